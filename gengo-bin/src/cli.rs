@@ -2,12 +2,20 @@
 use chromaterm::{colors, prelude::*};
 use clap::Error as ClapError;
 use clap::{Parser, Subcommand, ValueEnum};
-use gengo::{Analysis, Builder, Directory, Git, analysis::SummaryOpts};
+use gengo::{
+    Analysis, Builder, DetectabilityPolicy, Directory, DirectoryBuilder, FileSource, GitBuilder,
+    HistoryBuilder, Language, Overrides, Sample, Sampling, Submodules, SymlinkPolicy,
+    analysis::{Change, Diff, Summary, SummaryOpts},
+    language::Category,
+};
 use indexmap::IndexMap;
 #[cfg(feature = "color")]
 use relative_luminance::Luminance;
 use std::error::Error as BaseError;
+use std::fs;
 use std::io::{self, Write};
+use std::num::{NonZeroU64, NonZeroUsize};
+use std::str::FromStr;
 
 pub fn new() -> CLI {
     CLI::parse()
@@ -28,9 +36,10 @@ pub struct CLI {
     ///
     /// This is useful for large files that can impact performance.
     ///
-    /// The format is in bytes. The default is 1 MiB.
-    #[arg(short = 'l', long, default_value = "1048576", global = true)]
-    read_limit: usize,
+    /// The format is in bytes. The default is the `read-limit` from
+    /// `.gengo.toml`, or 1 MiB.
+    #[arg(short = 'l', long, global = true)]
+    read_limit: Option<usize>,
     /// Report on all files, even if they are not detectable.
     ///
     /// This only applies to the pretty format, as machine-readable
@@ -43,6 +52,13 @@ pub struct CLI {
     /// formats always include detailed statistics.
     #[arg(short = 'b', long, global = true)]
     breakdown: bool,
+    /// Include a summary of binary files, such as images, fonts, and
+    /// compiled artifacts.
+    ///
+    /// This only applies to the pretty format, as machine-readable
+    /// formats always include binary files.
+    #[arg(short = 'B', long, global = true)]
+    binary: bool,
     /// Control when colors are displayed.
     #[cfg(feature = "color")]
     #[arg(long, default_value = "auto", global = true)]
@@ -50,10 +66,115 @@ pub struct CLI {
     /// The format to use for output.
     #[arg(short = 'F', long, default_value = "pretty", global = true)]
     format: Format,
+    /// Override the detected attributes of files matching a glob.
+    ///
+    /// The format is `GLOB=ATTR[,ATTR]`, where each attribute is written like
+    /// a gengo attribute in `.gitattributes`: `generated` sets it, `-generated`
+    /// unsets it, and `language=PlainText` sets a value. The `gengo-` prefix is
+    /// optional. The available attributes are `language`, `generated`,
+    /// `vendored`, `documentation`, `detectable`, and `binary`.
+    ///
+    /// Can be repeated. Later rules take precedence, and rules take precedence
+    /// over attributes from the file source.
+    #[arg(long = "override", value_name = "GLOB=ATTRS", value_parser = parse_override_rule, global = true)]
+    overrides: Vec<(String, Overrides)>,
+    /// Make a language or category detectable, including it in statistics.
+    ///
+    /// Languages use the same names as the `gengo-language` attribute, like
+    /// `Sql`, and categories are lowercase, like `data`. Rules for languages
+    /// take precedence over rules for categories. By default, `data` and
+    /// `prose` are not detectable.
+    ///
+    /// Can be repeated.
+    #[arg(long, value_name = "LANGUAGE|CATEGORY", global = true)]
+    detectable: Vec<DetectabilityTarget>,
+    /// Make a language or category undetectable, excluding it from statistics.
+    ///
+    /// See `--detectable` for the format. Can be repeated.
+    #[arg(long, value_name = "LANGUAGE|CATEGORY", global = true)]
+    undetectable: Vec<DetectabilityTarget>,
+    /// Don't read the `.gengo.toml` configuration file.
+    #[arg(long, global = true)]
+    no_config: bool,
+    /// Fail if any files were skipped because of errors, like unreadable
+    /// files or missing git objects.
+    ///
+    /// Without this, skipped files are counted in a warning.
+    #[arg(long, global = true)]
+    strict: bool,
+}
+
+/// A language or category for the detectability policy.
+#[derive(Clone, Debug)]
+enum DetectabilityTarget {
+    Category(Category),
+    Language(Language),
+}
+
+impl FromStr for DetectabilityTarget {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.parse()
+            .map(Self::Category)
+            .or_else(|_| s.parse().map(Self::Language))
+            .map_err(|_| format!("unknown language or category {s:?}"))
+    }
 }
 
 #[derive(Subcommand)]
 enum Commands {
+    #[command(flatten)]
+    Analysis(AnalysisCommands),
+    /// Compare the languages of two revisions, directories, or saved JSON
+    /// analyses.
+    ///
+    /// Use `--breakdown` to also list the files that were added, removed,
+    /// changed, or reclassified.
+    Diff {
+        /// The old revision, directory, or JSON file.
+        old: String,
+        /// The new revision, directory, or JSON file.
+        new: String,
+        /// What the old and new arguments are.
+        #[arg(short = 's', long, default_value = "git")]
+        source: DiffSource,
+        /// The path to the repository, when comparing revisions.
+        #[arg(short = 'R', long, default_value = ".")]
+        repository: String,
+    },
+    /// Show how the languages of a git repository changed over its history.
+    ///
+    /// Commits are sampled along the first-parent history of the revision,
+    /// and are listed from oldest to newest. By default, a commit is sampled
+    /// every 30 days. Files that didn't change between samples are only
+    /// analyzed once.
+    History {
+        /// The path to the repository to analyze.
+        #[arg(short = 'R', long, default_value = ".")]
+        repository: String,
+        /// The git revision whose history is analyzed.
+        #[arg(short = 'r', long = "rev", default_value = "HEAD")]
+        revision: String,
+        /// Sample every Nth commit.
+        #[arg(long, value_name = "N", conflicts_with_all = ["tags", "interval"])]
+        every: Option<NonZeroUsize>,
+        /// Sample the commits that tags point to.
+        #[arg(long, conflicts_with = "interval")]
+        tags: bool,
+        /// Sample a commit every this many days.
+        #[arg(long, value_name = "DAYS")]
+        interval: Option<NonZeroU64>,
+        /// Only sample commits since a date, like `2024-01-31` or
+        /// `2 years ago`.
+        #[arg(long, value_name = "DATE")]
+        since: Option<String>,
+    },
+}
+
+/// The commands that analyze a single file source.
+#[derive(Subcommand)]
+enum AnalysisCommands {
     /// Analyze a git repository.
     Git {
         /// The path to the repository to analyze.
@@ -62,15 +183,134 @@ enum Commands {
         /// The git revision to analyze.
         #[arg(short = 'r', long = "rev", default_value = "HEAD")]
         revision: String,
+        /// Analyze the files staged in the index instead of a revision.
+        #[arg(long, conflicts_with_all = ["revision", "worktree"])]
+        index: bool,
+        /// Analyze the files in the worktree instead of a revision,
+        /// including untracked files that aren't ignored.
+        #[arg(long, conflicts_with = "revision")]
+        worktree: bool,
+        /// Only analyze files matching a pathspec, like `src` or
+        /// `:(exclude)vendor`. Pathspecs are relative to the root of the
+        /// repository. Can be repeated.
+        #[arg(long = "path", value_name = "PATHSPEC")]
+        paths: Vec<String>,
+        /// Only analyze the files in a directory of the repository, and
+        /// display their paths relative to it.
+        #[arg(long, value_name = "DIR")]
+        reroot: Option<String>,
+        /// Only analyze the files that were added or modified since the
+        /// merge base of a revision and the analyzed revision, like the
+        /// files that a pull request changes.
+        #[arg(long, value_name = "BASE", conflicts_with = "worktree")]
+        changed_since: Option<String>,
+        /// Weight the changed files by the size of their added or modified
+        /// lines, instead of their full size.
+        #[arg(long, requires = "changed_since")]
+        changed_lines: bool,
+        /// How to analyze submodules. Submodules are analyzed at the
+        /// commits that the revision pins them to.
+        #[arg(long, default_value = "exclude")]
+        submodules: SubmoduleMode,
+        /// How to analyze symbolic links. Links are resolved within the
+        /// repository.
+        #[arg(long, default_value = "skip")]
+        symlinks: SymlinkMode,
     },
     /// ***BETA*** Analyze a directory.
     Directory {
         /// The path to the directory to analyze.
         #[arg(short = 'D', long, default_value = ".")]
         directory: String,
+        /// How to display the paths of files.
+        ///
+        /// Attributes and globs are always matched against paths relative
+        /// to the directory.
+        #[arg(long, default_value = "relative")]
+        paths: PathStyle,
+        /// Include hidden files and directories.
+        #[arg(long)]
+        hidden: bool,
+        /// Follow symbolic links.
+        #[arg(short = 'L', long)]
+        follow_links: bool,
+        /// How to analyze symbolic links to files.
+        #[arg(long, default_value = "resolve")]
+        symlinks: SymlinkMode,
+        /// The maximum depth to walk. Files in the directory have a depth
+        /// of 1.
+        #[arg(long)]
+        max_depth: Option<usize>,
+        /// Don't respect `.gitignore` files, `.git/info/exclude`, or the
+        /// global git ignore file.
+        #[arg(long)]
+        no_gitignore: bool,
+        /// Don't respect `.ignore` files.
+        #[arg(long)]
+        no_ignore: bool,
+        /// Don't respect `.gengoignore` files.
+        #[arg(long)]
+        no_gengoignore: bool,
+        /// Only include files matching a glob. Can be repeated.
+        #[arg(long, value_name = "GLOB")]
+        include: Vec<String>,
+        /// Exclude files matching a glob. Takes precedence over `--include`.
+        /// Can be repeated.
+        #[arg(long, value_name = "GLOB")]
+        exclude: Vec<String>,
     },
 }
 
+#[derive(ValueEnum, Debug, Clone)]
+enum DiffSource {
+    /// Git revisions.
+    Git,
+    /// Directories.
+    Directory,
+    /// JSON files saved from `--format json`.
+    Json,
+}
+
+#[derive(ValueEnum, Debug, Clone)]
+enum SubmoduleMode {
+    /// Skip submodules.
+    Exclude,
+    /// Analyze submodules like the rest of the repository.
+    Include,
+    /// Analyze submodules, and mark their files as vendored.
+    Vendored,
+}
+
+#[derive(ValueEnum, Debug, Clone)]
+enum SymlinkMode {
+    /// Skip symbolic links.
+    Skip,
+    /// Analyze a link as a file containing the path of its target.
+    Text,
+    /// Analyze a link as the file that it points to.
+    Resolve,
+}
+
+impl SymlinkMode {
+    fn policy(&self) -> SymlinkPolicy {
+        match self {
+            SymlinkMode::Skip => SymlinkPolicy::Skip,
+            SymlinkMode::Text => SymlinkPolicy::Text,
+            SymlinkMode::Resolve => SymlinkPolicy::Resolve,
+        }
+    }
+}
+
+#[derive(ValueEnum, Debug, Clone)]
+enum PathStyle {
+    /// Relative to the analyzed directory.
+    Relative,
+    /// Prefixed with the directory as it was given.
+    Prefixed,
+    /// Absolute paths.
+    Absolute,
+}
+
 #[cfg(feature = "color")]
 #[derive(ValueEnum, Debug, Clone)]
 enum ColorControl {
@@ -107,14 +347,50 @@ impl CLI {
                 Never => chromaterm::config::use_color_support(ColorSupport::None),
             }
         }
-        let results = self.command.analyze(self.read_limit);
+        let command = match &self.command {
+            Commands::Analysis(command) => command,
+            Commands::Diff {
+                old,
+                new,
+                source,
+                repository,
+            } => {
+                let (old, new) = match self.analyze_diff(source, repository, old, new) {
+                    Ok(analyses) => analyses,
+                    Err(e) => return Self::report_error(e.as_ref(), err),
+                };
+                self.report_diagnostics(&old, &mut err)?;
+                self.report_diagnostics(&new, &mut err)?;
+                let mut summary_opts: SummaryOpts = Default::default();
+                summary_opts.all = self.all;
+                let diff = old.diff_with(&new, summary_opts);
+                return self.run_diff(&diff, out);
+            }
+            Commands::History {
+                repository,
+                revision,
+                every,
+                tags,
+                interval,
+                since,
+            } => {
+                let sampling = history_sampling(*every, *tags, *interval);
+                let analyses = match self.analyze_history(repository, revision, sampling, since) {
+                    Ok(analyses) => analyses,
+                    Err(e) => return Self::report_error(e.as_ref(), err),
+                };
+                for (_, analysis) in &analyses {
+                    self.report_diagnostics(analysis, &mut err)?;
+                }
+                return self.run_history(&analyses, out);
+            }
+        };
+        let results = command.analyze(self);
         let results = match results {
             Ok(results) => results,
-            Err(e) => {
-                writeln!(err, "failed to analyze repository: {}", e)?;
-                return Ok(());
-            }
+            Err(e) => return Self::report_error(e.as_ref(), err),
         };
+        self.report_diagnostics(&results, &mut err)?;
 
         match self.format {
             Format::Pretty => (),
@@ -124,8 +400,30 @@ impl CLI {
         let mut summary_opts: SummaryOpts = Default::default();
         summary_opts.all = self.all;
         let summary = results.summary_with(summary_opts);
+        self.write_summary(&summary, "", &mut out)?;
+
+        if self.binary && results.iter().any(|(_, entry)| entry.binary()) {
+            writeln!(out)?;
+            self.run_binary_summary(&mut out, &results)?;
+        }
+
+        if self.breakdown {
+            writeln!(out)?;
+            self.run_breakdown(out, err, results)?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes a line for each language of a summary, from largest to
+    /// smallest, after `indent`.
+    fn write_summary(
+        &self,
+        summary: &Summary,
+        indent: &str,
+        mut out: impl Write,
+    ) -> Result<(), io::Error> {
         let total = summary.total();
-        let total = total as f64;
 
         let summary = {
             let mut summary: Vec<(_, _)> = summary.iter().collect();
@@ -134,7 +432,7 @@ impl CLI {
         };
 
         for (language, size) in summary.iter() {
-            let percentage = (*size * 100) as f64 / total;
+            let percentage = percentage(**size, total);
             #[cfg(feature = "color")]
             let color = {
                 let (r, g, b) = language.rgb();
@@ -146,14 +444,236 @@ impl CLI {
             let stats = format!("{:>6.2}% {}", percentage, size);
             let line = format!("{:<15} {}", stats, language.name());
             let line = self.colorize(&line, &color);
-            writeln!(out, "{}", line)?;
+            writeln!(out, "{indent}{}", line)?;
         }
+        Ok(())
+    }
 
-        if self.breakdown {
-            writeln!(out)?;
-            self.run_breakdown(out, err, results)?;
+    /// Reports an error that stopped the analysis, and its sources.
+    fn report_error(e: &dyn BaseError, mut err: impl Write) -> Result<(), io::Error> {
+        writeln!(err, "failed to analyze repository: {}", e)?;
+        let mut source = e.source();
+        while let Some(e) = source {
+            writeln!(err, "  caused by: {}", e)?;
+            source = e.source();
         }
+        Ok(())
+    }
+
+    /// Analyzes the old and new sides of a diff.
+    fn analyze_diff(
+        &self,
+        source: &DiffSource,
+        repository: &str,
+        old: &str,
+        new: &str,
+    ) -> Result<(Analysis, Analysis), Box<dyn BaseError>> {
+        Ok((
+            self.analyze_diff_side(source, repository, old)?,
+            self.analyze_diff_side(source, repository, new)?,
+        ))
+    }
 
+    fn analyze_diff_side(
+        &self,
+        source: &DiffSource,
+        repository: &str,
+        side: &str,
+    ) -> Result<Analysis, Box<dyn BaseError>> {
+        match source {
+            DiffSource::Git => {
+                let git = GitBuilder::new(repository, side).build()?;
+                let gengo = self.configure(Builder::new(git)).build()?;
+                gengo.analyze()
+            }
+            DiffSource::Directory => {
+                let buf_size = self
+                    .read_limit
+                    .unwrap_or(Builder::<Directory>::DEFAULT_READ_LIMIT);
+                let directory = DirectoryBuilder::new(side, buf_size).build()?;
+                let gengo = self.configure(Builder::new(directory)).build()?;
+                gengo.analyze()
+            }
+            DiffSource::Json => {
+                let json = fs::read(side)?;
+                Ok(serde_json::from_slice(&json)?)
+            }
+        }
+    }
+
+    /// Samples the history of a repository and analyzes each sample.
+    fn analyze_history(
+        &self,
+        repository: &str,
+        revision: &str,
+        sampling: Sampling,
+        since: &Option<String>,
+    ) -> Result<Vec<(Sample, Analysis)>, Box<dyn BaseError>> {
+        let builder = HistoryBuilder::new(repository, revision).sampling(sampling);
+        let builder = match since {
+            Some(date) => builder.since(date),
+            None => builder,
+        };
+        builder
+            .build()?
+            .analyze(|git| self.configure(Builder::new(git.build()?)).build())
+    }
+
+    /// Writes the summary of each sample of a history.
+    fn run_history(
+        &self,
+        analyses: &[(Sample, Analysis)],
+        mut out: impl Write,
+    ) -> Result<(), io::Error> {
+        let summary = |analysis: &Analysis| {
+            let mut summary_opts: SummaryOpts = Default::default();
+            summary_opts.all = self.all;
+            analysis.summary_with(summary_opts)
+        };
+        if let Format::Json = self.format {
+            let history: Vec<_> = analyses
+                .iter()
+                .map(|(sample, analysis)| {
+                    let summary: serde_json::Map<_, _> = summary(analysis)
+                        .iter()
+                        .map(|(language, size)| (language.name().to_string(), (*size).into()))
+                        .collect();
+                    serde_json::json!({
+                        "id": sample.id(),
+                        "time": sample.time(),
+                        "tag": sample.tag(),
+                        "summary": summary,
+                    })
+                })
+                .collect();
+            match serde_json::to_string(&history) {
+                Ok(s) => writeln!(out, "{s}")?,
+                Err(e) => writeln!(out, "failed to serialize to JSON: {e}")?,
+            }
+            return Ok(());
+        }
+
+        for (i, (sample, analysis)) in analyses.iter().enumerate() {
+            if i > 0 {
+                writeln!(out)?;
+            }
+            let id = sample.short_id();
+            match sample.tag() {
+                Some(tag) => writeln!(out, "{} {id} ({tag})", sample.date())?,
+                None => writeln!(out, "{} {id}", sample.date())?,
+            }
+            self.write_summary(&summary(analysis), "  ", &mut out)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the language deltas of a diff, and the changed files if
+    /// `--breakdown` was used.
+    fn run_diff(&self, diff: &Diff, mut out: impl Write) -> Result<(), io::Error> {
+        if let Format::Json = self.format {
+            match serde_json::to_string(diff) {
+                Ok(s) => writeln!(out, "{s}")?,
+                Err(e) => writeln!(out, "failed to serialize to JSON: {e}")?,
+            }
+            return Ok(());
+        }
+
+        let mut languages: Vec<_> = diff.languages().iter().collect();
+        languages.sort_by_key(|language| (-language.size_delta.abs(), language.language.name()));
+        for language in languages {
+            #[cfg(feature = "color")]
+            let color = {
+                let (r, g, b) = language.language.rgb();
+                chromaterm::colors::True::from_rgb(r, g, b)
+            };
+            #[cfg(not(feature = "color"))]
+            let color = ();
+
+            let status = if language.is_added() {
+                " (new)"
+            } else if language.is_removed() {
+                " (removed)"
+            } else {
+                ""
+            };
+            let stats = format!(
+                "{:>+10} {:>+5} files",
+                language.size_delta, language.files_delta
+            );
+            let line = format!("{stats} {}{status}", language.language.name());
+            writeln!(out, "{}", self.colorize(&line, &color))?;
+        }
+
+        if !self.breakdown || diff.files().is_empty() {
+            return Ok(());
+        }
+        writeln!(out)?;
+        for file in diff.files() {
+            let path = file.path().display();
+            let language = |entry: Option<&gengo::Entry>| {
+                entry
+                    .and_then(|entry| entry.language())
+                    .map_or("unknown", |language| language.name())
+            };
+            let (old, new) = (language(file.before()), language(file.after()));
+            match file.change() {
+                Change::Added => writeln!(out, "A {path} ({new})")?,
+                Change::Removed => writeln!(out, "D {path} ({old})")?,
+                Change::Reclassified if old != new => writeln!(out, "R {path} ({old} -> {new})")?,
+                Change::Reclassified => writeln!(out, "R {path} ({new})")?,
+                _ => writeln!(out, "M {path} ({new})")?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Reports files that were skipped because of errors. In strict mode,
+    /// each skipped file is reported and an error is returned.
+    fn report_diagnostics(&self, results: &Analysis, mut err: impl Write) -> Result<(), io::Error> {
+        let diagnostics = results.diagnostics();
+        if diagnostics.is_empty() {
+            return Ok(());
+        }
+        let count = diagnostics.len();
+        let noun = if count == 1 { "file" } else { "files" };
+        if !self.strict {
+            writeln!(
+                err,
+                "warning: skipped {count} {noun} because of errors (use --strict for details)"
+            )?;
+            return Ok(());
+        }
+        for diagnostic in diagnostics {
+            writeln!(err, "error: {diagnostic}")?;
+        }
+        Err(io::Error::other(format!(
+            "skipped {count} {noun} because of errors"
+        )))
+    }
+
+    fn run_binary_summary(&self, mut out: impl Write, results: &Analysis) -> Result<(), io::Error> {
+        let summary = results.binary_summary();
+        let total = summary.total();
+
+        let summary = {
+            let mut summary: Vec<(_, _)> = summary
+                .iter()
+                .map(|(format, stats)| {
+                    let name = format.map_or("Unknown", |format| format.name());
+                    (name, *stats)
+                })
+                .collect();
+            summary.sort_by_key(|(name, stats)| (usize::MAX - stats.size, *name));
+            summary
+        };
+
+        writeln!(out, "Binary files")?;
+        for (name, stats) in summary {
+            let percentage = percentage(stats.size, total);
+            let noun = if stats.files == 1 { "file" } else { "files" };
+            let line = format!("{:>6.2}% {}", percentage, stats.size);
+            writeln!(out, "{:<15} {} ({} {noun})", line, name, stats.files)?;
+        }
         Ok(())
     }
 
@@ -182,11 +702,13 @@ impl CLI {
         let files_per_language = {
             let mut files_per_language = IndexMap::new();
             for (path, entry) in results.iter() {
-                if !(self.all || entry.detectable()) {
+                if !(self.all || entry.detectable()) || entry.binary() {
                     continue;
                 }
 
-                let language = entry.language();
+                let Some(language) = entry.language() else {
+                    continue;
+                };
                 let language_files = files_per_language.entry(language).or_insert_with(Vec::new);
                 language_files.push(path);
             }
@@ -228,6 +750,44 @@ impl CLI {
         Ok(())
     }
 
+    /// Applies the options that are shared by all file sources.
+    fn configure<FS: for<'fs> FileSource<'fs>>(&self, builder: Builder<FS>) -> Builder<FS> {
+        let builder = builder
+            .override_rules(self.overrides.iter().cloned())
+            .read_config(!self.no_config);
+        let builder = match self.read_limit {
+            Some(read_limit) => builder.read_limit(read_limit),
+            None => builder,
+        };
+        match self.detectability_policy() {
+            Some(policy) => builder.detectability(policy),
+            None => builder,
+        }
+    }
+
+    /// Creates a detectability policy from the CLI flags. Returns `None` if no
+    /// flags were used, so that the configuration file's policy is used instead.
+    fn detectability_policy(&self) -> Option<DetectabilityPolicy> {
+        if self.detectable.is_empty() && self.undetectable.is_empty() {
+            return None;
+        }
+        let rules = self
+            .detectable
+            .iter()
+            .map(|target| (target, true))
+            .chain(self.undetectable.iter().map(|target| (target, false)));
+        let policy = rules.fold(
+            DetectabilityPolicy::default(),
+            |policy, (target, detectable)| match target {
+                DetectabilityTarget::Category(category) => {
+                    policy.category(category.clone(), detectable)
+                }
+                DetectabilityTarget::Language(language) => policy.language(*language, detectable),
+            },
+        );
+        Some(policy)
+    }
+
     #[cfg(feature = "color")]
     fn colorize(&self, s: &str, color: &colors::True) -> String {
         use chromaterm::{Color, colors::True};
@@ -252,24 +812,156 @@ impl CLI {
     }
 }
 
-impl Commands {
-    fn analyze(&self, read_limit: usize) -> Result<Analysis, Box<dyn BaseError>> {
+impl AnalysisCommands {
+    fn analyze(&self, cli: &CLI) -> Result<Analysis, Box<dyn BaseError>> {
         match self {
-            Commands::Git {
+            AnalysisCommands::Git {
                 repository,
                 revision,
+                index,
+                worktree,
+                paths,
+                reroot,
+                changed_since,
+                changed_lines,
+                submodules,
+                symlinks,
             } => {
-                let git = Git::new(repository, revision)?;
-                let gengo = Builder::new(git).read_limit(read_limit).build()?;
+                let submodules = match submodules {
+                    SubmoduleMode::Exclude => Submodules::Exclude,
+                    SubmoduleMode::Include => Submodules::Include,
+                    SubmoduleMode::Vendored => Submodules::Vendored,
+                };
+                let builder = if *index {
+                    GitBuilder::index(repository)
+                } else if *worktree {
+                    GitBuilder::worktree(repository)
+                } else {
+                    GitBuilder::new(repository, revision)
+                };
+                let builder = paths
+                    .iter()
+                    .fold(builder, |builder, pathspec| builder.pathspec(pathspec));
+                let builder = match reroot {
+                    Some(dir) => builder.reroot(dir),
+                    None => builder,
+                };
+                let builder = match changed_since {
+                    Some(base) => builder.changed_since(base).changed_lines(*changed_lines),
+                    None => builder,
+                };
+                let git = builder
+                    .submodules(submodules)
+                    .symlinks(symlinks.policy())
+                    .build()?;
+                let gengo = cli.configure(Builder::new(git)).build()?;
                 gengo.analyze()
             }
-            Commands::Directory { directory } => {
-                let directory = Directory::new(directory, read_limit)?;
-                let gengo = Builder::new(directory).read_limit(read_limit).build()?;
-                gengo.analyze()
+            AnalysisCommands::Directory {
+                directory,
+                paths,
+                hidden,
+                follow_links,
+                symlinks,
+                max_depth,
+                no_gitignore,
+                no_ignore,
+                no_gengoignore,
+                include,
+                exclude,
+            } => {
+                let buf_size = cli
+                    .read_limit
+                    .unwrap_or(Builder::<Directory>::DEFAULT_READ_LIMIT);
+                let builder = DirectoryBuilder::new(directory, buf_size)
+                    .hidden(*hidden)
+                    .follow_links(*follow_links)
+                    .symlinks(symlinks.policy())
+                    .max_depth(*max_depth)
+                    .git_ignore(!no_gitignore)
+                    .ignore(!no_ignore)
+                    .gengo_ignore(!no_gengoignore);
+                let builder = include
+                    .iter()
+                    .fold(builder, |builder, glob| builder.include(glob));
+                let builder = exclude
+                    .iter()
+                    .fold(builder, |builder, glob| builder.exclude(glob));
+                let source = builder.build()?;
+                let gengo = cli.configure(Builder::new(source)).build()?;
+                let analysis = gengo.analyze()?;
+                let analysis = match paths {
+                    PathStyle::Relative => analysis,
+                    PathStyle::Prefixed => analysis.prefix_paths(directory),
+                    PathStyle::Absolute => analysis.prefix_paths(fs::canonicalize(directory)?),
+                };
+                Ok(analysis)
+            }
+        }
+    }
+}
+
+/// Chooses how to sample a history from the `history` flags. A commit is
+/// sampled every 30 days by default.
+fn history_sampling(
+    every: Option<NonZeroUsize>,
+    tags: bool,
+    interval: Option<NonZeroU64>,
+) -> Sampling {
+    const DAY: u64 = 24 * 60 * 60;
+    match (every, tags, interval) {
+        (Some(n), _, _) => Sampling::Commits(n.get()),
+        (_, true, _) => Sampling::Tags,
+        (_, _, Some(days)) => Sampling::Interval(days.get().saturating_mul(DAY)),
+        (None, false, None) => Sampling::Interval(30 * DAY),
+    }
+}
+
+/// The percentage of `total` that `size` is. Empty totals are 0%, so that
+/// empty files don't show up as `NaN%`.
+fn percentage(size: usize, total: usize) -> f64 {
+    if total == 0 {
+        return 0.0;
+    }
+    (size * 100) as f64 / total as f64
+}
+
+/// Parses an override rule in the format `GLOB=ATTR[,ATTR]`.
+fn parse_override_rule(rule: &str) -> Result<(String, Overrides), String> {
+    let (glob, attrs) = rule
+        .split_once('=')
+        .ok_or_else(|| format!("expected GLOB=ATTRS, got {rule:?}"))?;
+    let mut overrides = Overrides::default();
+    for attr in attrs.split(',').map(str::trim) {
+        let (unset, attr) = match attr.strip_prefix('-') {
+            Some(attr) => (true, attr),
+            None => (false, attr),
+        };
+        let attr = attr.strip_prefix("gengo-").unwrap_or(attr);
+        let flag = Some(!unset);
+        match attr.split_once('=') {
+            Some(("language", _)) if unset => {
+                return Err("language cannot be unset".into());
+            }
+            Some(("language", language)) => {
+                let language = language
+                    .parse()
+                    .map_err(|_| format!("unknown language {language:?}"))?;
+                overrides.language = Some(language);
             }
+            Some((attr, _)) => return Err(format!("{attr:?} does not take a value")),
+            None => match attr {
+                "documentation" => overrides.is_documentation = flag,
+                "generated" => overrides.is_generated = flag,
+                "vendored" => overrides.is_vendored = flag,
+                "detectable" => overrides.is_detectable = flag,
+                "binary" => overrides.is_binary = flag,
+                "language" => return Err("language requires a value".into()),
+                attr => return Err(format!("unknown attribute {attr:?}")),
+            },
         }
     }
+    Ok((glob.to_string(), overrides))
 }
 
 #[cfg(feature = "color")]
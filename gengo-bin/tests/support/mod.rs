@@ -0,0 +1,79 @@
+//! Fixtures shared by the CLI tests. Each fixture lives in its own temporary
+//! directory, which is removed when it's dropped, so tests can run in
+//! parallel.
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use std::process::Command;
+use tempfile::TempDir;
+
+pub struct NullWriter;
+
+impl Write for NullWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Runs the CLI and returns what it wrote to stdout.
+pub fn run(args: &[&str]) -> String {
+    let cli = gengo_bin::cli::try_new_from(args).unwrap();
+    let mut stdout = Vec::new();
+    cli.run(&mut stdout, NullWriter).unwrap();
+    String::from_utf8(stdout).unwrap()
+}
+
+/// Creates a directory with the given files.
+pub fn directory(files: &[(&str, &[u8])]) -> TempDir {
+    let root = tempfile::tempdir().unwrap();
+    write(root.path(), files);
+    root
+}
+
+/// Writes files under `root`, creating their parent directories.
+pub fn write(root: &Path, files: &[(&str, &[u8])]) {
+    for (path, contents) in files {
+        let path = root.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+}
+
+/// Creates an empty git repository.
+pub fn git_repository() -> TempDir {
+    let root = directory(&[]);
+    git(root.path(), &["init", "--quiet"], None);
+    root
+}
+
+/// Commits everything in `root` with a fixed date, like `2024-01-31`.
+pub fn commit_at(root: &Path, date: &str) {
+    git(root, &["add", "."], None);
+    git(root, &["commit", "--quiet", "-m", date], Some(date));
+}
+
+/// Runs a git command in `root`, with a fixed author and committer date if
+/// one is given.
+pub fn git(root: &Path, args: &[&str], date: Option<&str>) {
+    let mut command = Command::new("git");
+    command
+        .args([
+            "-c",
+            "user.name=gengo",
+            "-c",
+            "user.email=gengo@example.com",
+        ])
+        .args(args)
+        .current_dir(root);
+    if let Some(date) = date {
+        let date = format!("{date}T00:00:00Z");
+        command
+            .env("GIT_AUTHOR_DATE", &date)
+            .env("GIT_COMMITTER_DATE", &date);
+    }
+    let status = command.output().unwrap().status;
+    assert!(status.success(), "git {args:?} failed");
+}
@@ -1,18 +1,9 @@
 use insta::{assert_json_snapshot, assert_snapshot};
-use std::io::{self, Write};
+use support::{NullWriter, run};
 
-const ROOT: &str = env!("CARGO_MANIFEST_DIR");
-
-struct NullWriter;
+mod support;
 
-impl Write for NullWriter {
-    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-        Ok(buf.len())
-    }
-    fn flush(&mut self) -> io::Result<()> {
-        Ok(())
-    }
-}
+const ROOT: &str = env!("CARGO_MANIFEST_DIR");
 
 macro_rules! assert_stdout_snapshot {
     ($cli_args:expr $(,)?) => {{
@@ -96,3 +87,213 @@ fn test_json_output_on_javascript_repo() {
         ROOT,
     ]);
 }
+
+#[test]
+fn test_override_rule_parsing() {
+    let valid = [
+        "*.js=generated",
+        "dist/**=-vendored,gengo-language=PlainText",
+    ];
+    for rule in valid {
+        assert!(gengo_bin::cli::try_new_from(&["gengo", "--override", rule, "git"]).is_ok());
+    }
+    let invalid = [
+        "*.js",
+        "*.js=bogus",
+        "*.js=language=Bogus",
+        "*.js=-language=Rust",
+    ];
+    for rule in invalid {
+        assert!(gengo_bin::cli::try_new_from(&["gengo", "--override", rule, "git"]).is_err());
+    }
+}
+
+#[test]
+fn test_detectability_parsing() {
+    let args = [
+        "--detectable",
+        "Sql",
+        "--detectable",
+        "data",
+        "--undetectable",
+        "Makefile",
+    ];
+    assert!(gengo_bin::cli::try_new_from(&[&["gengo"], &args[..], &["git"]].concat()).is_ok());
+    for target in ["sql", "Data", "bogus"] {
+        assert!(gengo_bin::cli::try_new_from(&["gengo", "--detectable", target, "git"]).is_err());
+    }
+}
+
+#[test]
+fn test_directory_path_styles() {
+    let paths = |style: &str| {
+        let directory = format!("{ROOT}/src");
+        let args = [
+            "gengo",
+            "--format",
+            "json",
+            "directory",
+            "-D",
+            &directory,
+            "--paths",
+            style,
+        ];
+        let cli = gengo_bin::cli::try_new_from(&args).unwrap();
+        let mut stdout = Vec::new();
+        cli.run(&mut stdout, NullWriter).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&stdout).unwrap();
+        let mut paths: Vec<_> = json.as_object().unwrap().keys().cloned().collect();
+        paths.sort();
+        paths
+    };
+
+    let relative = paths("relative");
+    assert!(relative.contains(&"lib.rs".to_string()));
+    assert!(paths("prefixed").contains(&format!("{ROOT}/src/lib.rs")));
+    assert_eq!(paths("absolute").len(), relative.len());
+}
+
+#[test]
+fn test_binary_summary_of_empty_files() {
+    let root = support::directory(&[("main.rs", b"fn main() {}\n"), ("empty.png", b"")]);
+
+    let stdout = run(&[
+        "gengo",
+        "-B",
+        "directory",
+        "-D",
+        root.path().to_str().unwrap(),
+    ]);
+    assert!(stdout.contains("Binary files\n  0.00% 0"), "{stdout}");
+    assert!(!stdout.contains("NaN"));
+}
+
+#[test]
+#[cfg(unix)]
+fn test_strict_fails_on_skipped_files() {
+    let root = support::directory(&[("main.rs", b"fn main() {}\n")]);
+    std::os::unix::fs::symlink("missing.rs", root.path().join("broken.rs")).unwrap();
+    let root = root.path().to_str().unwrap();
+
+    let cli = gengo_bin::cli::try_new_from(&["gengo", "directory", "-D", root]).unwrap();
+    let mut stderr = Vec::new();
+    cli.run(NullWriter, &mut stderr).unwrap();
+    let stderr = String::from_utf8(stderr).unwrap();
+    assert!(stderr.contains("warning: skipped 1 file because of errors"));
+
+    // NOTE The JSON output only has the analyzed files, by path.
+    let cli =
+        gengo_bin::cli::try_new_from(&["gengo", "-F", "json", "directory", "-D", root]).unwrap();
+    let mut stdout = Vec::new();
+    let mut stderr = Vec::new();
+    cli.run(&mut stdout, &mut stderr).unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&stdout).unwrap();
+    let paths: Vec<_> = json.as_object().unwrap().keys().collect();
+    assert_eq!(paths, ["main.rs"]);
+    let stderr = String::from_utf8(stderr).unwrap();
+    assert!(stderr.contains("warning: skipped 1 file because of errors"));
+
+    let cli =
+        gengo_bin::cli::try_new_from(&["gengo", "--strict", "directory", "-D", root]).unwrap();
+    let mut stderr = Vec::new();
+    assert!(cli.run(NullWriter, &mut stderr).is_err());
+    let stderr = String::from_utf8(stderr).unwrap();
+    assert!(stderr.contains("broken.rs: failed to get size"));
+}
+
+#[test]
+fn test_diff_directories_and_json() {
+    let root = support::directory(&[
+        ("old/main.rs", b"fn main() {}\n"),
+        ("new/main.rs", b"fn main() {}\n"),
+        ("new/app.py", b"print(1)\n"),
+    ]);
+    let root = root.path();
+    let old = root.join("old");
+    let new = root.join("new");
+    let (old, new) = (old.to_str().unwrap(), new.to_str().unwrap());
+
+    let pretty = run(&["gengo", "-b", "diff", "-s", "directory", old, new]);
+    let lines: Vec<_> = pretty.lines().map(str::trim_end).collect();
+    assert_eq!(
+        lines,
+        [
+            "        +9    +1 files Python (new)",
+            "",
+            "A app.py (Python)"
+        ]
+    );
+
+    for (dir, path) in [(old, "old.json"), (new, "new.json")] {
+        let json = run(&["gengo", "-F", "json", "directory", "-D", dir]);
+        support::write(root, &[(path, json.as_bytes())]);
+    }
+    let old = root.join("old.json");
+    let new = root.join("new.json");
+    let json = run(&[
+        "gengo",
+        "-F",
+        "json",
+        "diff",
+        "-s",
+        "json",
+        old.to_str().unwrap(),
+        new.to_str().unwrap(),
+    ]);
+    let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+    assert_eq!(json["languages"][0]["language"]["name"], "Python");
+    assert_eq!(json["languages"][0]["size_delta"], 9);
+    assert_eq!(json["files"][0]["path"], "app.py");
+    assert_eq!(json["files"][0]["change"], "added");
+}
+
+#[test]
+fn test_history_of_git_repository() {
+    let root = support::git_repository();
+    let root = root.path();
+    for (path, contents, date) in [
+        ("main.rs", b"fn main() {}\n".as_slice(), "2024-01-01"),
+        ("app.py", b"print(1)\n", "2024-01-02"),
+        ("lib.rs", b"pub fn run() {}\n", "2024-03-01"),
+    ] {
+        support::write(root, &[(path, contents)]);
+        support::commit_at(root, date);
+    }
+    support::git(root, &["tag", "v1"], None);
+    let repository = root.to_str().unwrap();
+
+    let pretty = run(&["gengo", "--color", "never", "history", "-R", repository]);
+    let lines: Vec<_> = pretty
+        .lines()
+        .map(|line| {
+            // NOTE Commit IDs depend on the environment.
+            let mut words: Vec<_> = line.split_whitespace().collect();
+            if words.len() > 1 && words[0].starts_with("2024-") {
+                words[1] = "<id>";
+            }
+            words.join(" ")
+        })
+        .collect();
+    assert_eq!(
+        lines,
+        [
+            "2024-01-02 <id>",
+            "59.09% 13 Rust",
+            "40.91% 9 Python",
+            "",
+            "2024-03-01 <id> (v1)",
+            "76.32% 29 Rust",
+            "23.68% 9 Python",
+        ]
+    );
+
+    let json = run(&[
+        "gengo", "-F", "json", "history", "-R", repository, "--every", "1",
+    ]);
+    let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+    let samples = json.as_array().unwrap();
+    assert_eq!(samples.len(), 3);
+    assert_eq!(samples[0]["summary"], serde_json::json!({ "Rust": 13 }));
+    assert_eq!(samples[2]["tag"], "v1");
+    assert_eq!(samples[2]["summary"]["Rust"], 29);
+}
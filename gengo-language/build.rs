@@ -272,6 +272,30 @@ fn main() -> Result<(), Box<dyn Error>> {
         name_mixin.to_string(),
     )?;
 
+    let reverse_name_mappings =
+        language_definitions
+            .iter()
+            .map(|LanguageDefinition { variant, name, .. }| {
+                quote! {
+                    #name => Some(Self::#variant)
+                }
+            });
+    let from_name_mixin = quote! {
+        impl Language {
+            /// Gets the language with a name, as returned by [`Language::name`].
+            pub fn from_name(name: &str) -> Option<Self> {
+                match name {
+                    #(#reverse_name_mappings ,)*
+                    _ => None,
+                }
+            }
+        }
+    };
+    fs::write(
+        languages_target_dir.join("from_name_mixin.rs"),
+        from_name_mixin.to_string(),
+    )?;
+
     let reverse_variant_mappings =
         language_definitions
             .iter()
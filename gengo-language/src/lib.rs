@@ -22,6 +22,7 @@ macro_rules! _include {
 _include!("language.rs");
 _include!("category_mixin.rs");
 _include!("name_mixin.rs");
+_include!("from_name_mixin.rs");
 _include!("parse_variant_mixin.rs");
 _include!("color_hex_mixin.rs");
 _include!("color_rgb_mixin.rs");
@@ -49,17 +50,19 @@ impl Language {
         filename.map_or(vec![], Self::from_filename)
     }
 
+    /// The number of bytes at the start of a file that are checked for a
+    /// shebang.
+    pub const SHEBANG_LEN: usize = 50;
+
     /// Gets languages by a shebang.
     fn from_shebang(contents: &[u8]) -> Vec<Self> {
-        const MAX_SHEBANG_LENGTH: usize = 50;
-
         let mut lines = contents.split(|&c| c == b'\n');
         let first_line = lines.next().unwrap_or_default();
         if first_line.len() < 2 || first_line[0] != b'#' || first_line[1] != b'!' {
             return vec![];
         }
-        let first_line = if first_line.len() > MAX_SHEBANG_LENGTH {
-            &first_line[..MAX_SHEBANG_LENGTH]
+        let first_line = if first_line.len() > Self::SHEBANG_LEN {
+            &first_line[..Self::SHEBANG_LEN]
         } else {
             first_line
         };
@@ -152,6 +155,15 @@ impl Language {
         Self::from_path_extension(&path)
     }
 
+    /// Gets the languages that a file could be from its path and the first
+    /// [`Language::SHEBANG_LEN`] bytes of its contents, without checking
+    /// heuristics. If there are multiple candidates, [`Language::pick`] needs
+    /// more of the contents to choose between them.
+    pub fn candidates(path: impl AsRef<Path>, head: &[u8]) -> Vec<Self> {
+        let path = Self::maybe_strip_suffix_extensions(path.as_ref());
+        Self::find_simple(path, head)
+    }
+
     /// Picks the best guess from a file's name and contents.
     ///
     /// When checking heuristics, only the first `read_limit` bytes will be read.
@@ -246,6 +258,19 @@ impl serde::Serialize for Language {
     }
 }
 
+impl<'de> serde::Deserialize<'de> for Language {
+    /// Deserializes a language from its serialized attributes. Only the name
+    /// is used to find the language.
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let Deserialize { name } = Deserialize::deserialize(deserializer)?;
+        Self::from_name(&name)
+            .ok_or_else(|| serde::de::Error::custom(format!("unknown language: {name}")))
+    }
+}
+
 /// A category for a language.
 #[non_exhaustive]
 #[derive(Clone, Debug, serde::Deserialize, Eq, Hash, PartialEq, serde::Serialize)]
@@ -265,6 +290,24 @@ pub enum Category {
     Query,
 }
 
+impl FromStr for Category {
+    type Err = ParseError;
+
+    /// Converts a lowercase category name, like `programming`, into a category.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let category = match s {
+            "data" => Self::Data,
+            "markup" => Self::Markup,
+            "pattern" => Self::Pattern,
+            "programming" => Self::Programming,
+            "prose" => Self::Prose,
+            "query" => Self::Query,
+            _ => return Err(ParseError),
+        };
+        Ok(category)
+    }
+}
+
 /// Helper struct for serializing the attributes of a `Language`.
 #[derive(Debug, serde::Serialize)]
 struct Serialize {
@@ -274,6 +317,12 @@ struct Serialize {
     nerd_font_glyph: Option<&'static str>,
 }
 
+/// Helper struct for deserializing a `Language` from its attributes.
+#[derive(serde::Deserialize)]
+struct Deserialize {
+    name: String,
+}
+
 #[cfg(test)]
 mod language_tests {
     use super::*;
@@ -292,6 +341,43 @@ mod language_tests {
         assert!(languages.contains(&language));
     }
 
+    #[rstest(
+        path,
+        head,
+        count,
+        case::extension("main.rs", b"fn main() {}", 1),
+        case::suffix("main.rs.bak", b"", 1),
+        case::shebang("script", b"#!/usr/bin/env python3\n", 1),
+        case::unknown("unknown.zzz", b"", 0)
+    )]
+    fn test_candidates(path: &str, head: &[u8], count: usize) {
+        assert_eq!(Language::candidates(path, head).len(), count);
+    }
+
+    #[rstest(
+        language,
+        case(Language::Rust),
+        case(Language::CPlusPlus),
+        case(Language::PlainText)
+    )]
+    fn test_serde_round_trip(language: Language) {
+        let yaml = serde_yaml::to_string(&language).unwrap();
+        let deserialized: Language = serde_yaml::from_str(&yaml).unwrap();
+        assert_eq!(deserialized, language);
+    }
+
+    #[rstest(
+        input,
+        expected,
+        case("data", Ok(Category::Data)),
+        case("programming", Ok(Category::Programming)),
+        case("Programming", Err(ParseError)),
+        case("unknown", Err(ParseError))
+    )]
+    fn test_category_from_str(input: &str, expected: Result<Category, ParseError>) {
+        assert_eq!(input.parse(), expected);
+    }
+
     #[rstest(
         input,
         expected,
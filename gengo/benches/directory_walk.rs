@@ -0,0 +1,20 @@
+use criterion::{Criterion, criterion_group, criterion_main};
+use gengo::{Builder, Directory, DirectoryBuilder};
+
+fn directory_benchmark(c: &mut Criterion) {
+    // NOTE: We walk the whole workspace as a real-world example
+    let root = concat!(env!("CARGO_MANIFEST_DIR"), "/..");
+    for (name, parallel) in [("bridged", false), ("parallel", true)] {
+        let directory = DirectoryBuilder::new(root, Builder::<Directory>::DEFAULT_READ_LIMIT)
+            .parallel(parallel)
+            .build()
+            .unwrap();
+        let gengo = Builder::new(directory).build().unwrap();
+        c.bench_function(&format!("walk directory ({})", name), |b| {
+            b.iter(|| gengo.analyze().unwrap())
+        });
+    }
+}
+
+criterion_group!(benches, directory_benchmark);
+criterion_main!(benches);
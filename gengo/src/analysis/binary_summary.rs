@@ -0,0 +1,50 @@
+use crate::BinaryFormat;
+use indexmap::IndexMap;
+use indexmap::map::Iter as IndexMapIter;
+
+/// The summary of the binary files in an analysis.
+///
+/// Binary files with an unidentified format are grouped under `None`.
+#[derive(Debug)]
+pub struct BinarySummary(pub(super) IndexMap<Option<BinaryFormat>, BinaryStats>);
+
+impl BinarySummary {
+    /// Returns the total size of all binary files.
+    pub fn total(&self) -> usize {
+        self.0.values().map(|stats| stats.size).sum()
+    }
+
+    /// Returns an iterator over the formats and their stats.
+    pub fn iter(&self) -> Iter<'_> {
+        Iter(self.0.iter())
+    }
+}
+
+/// Statistics for a binary format.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct BinaryStats {
+    /// The number of files.
+    pub files: usize,
+    /// The total size of the files.
+    pub size: usize,
+}
+
+pub struct Iter<'map>(IndexMapIter<'map, Option<BinaryFormat>, BinaryStats>);
+
+impl<'map> Iterator for Iter<'map> {
+    type Item = (&'map Option<BinaryFormat>, &'map BinaryStats);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next()
+    }
+}
+
+impl<'map> IntoIterator for &'map BinarySummary {
+    type Item = (&'map Option<BinaryFormat>, &'map BinaryStats);
+    type IntoIter = Iter<'map>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
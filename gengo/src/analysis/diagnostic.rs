@@ -0,0 +1,199 @@
+use crate::{Error, ErrorKind};
+use serde::{Deserialize, Serialize};
+use std::error::Error as ErrorTrait;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// A file that was skipped because of an error.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Diagnostic {
+    path: Option<PathBuf>,
+    stage: Stage,
+    kind: Kind,
+    message: String,
+}
+
+impl Diagnostic {
+    /// Creates a new diagnostic. `path` should be relative to the root of the
+    /// file source, and can be `None` if the path is unknown.
+    pub fn new(
+        path: Option<PathBuf>,
+        stage: Stage,
+        kind: Kind,
+        message: impl Into<String>,
+    ) -> Self {
+        Self {
+            path,
+            stage,
+            kind,
+            message: message.into(),
+        }
+    }
+
+    /// Creates a new diagnostic from an error, using the error and its
+    /// sources for the message and the kind.
+    pub fn from_error(
+        path: Option<PathBuf>,
+        stage: Stage,
+        error: &(dyn ErrorTrait + 'static),
+    ) -> Self {
+        let mut message = error.to_string();
+        let mut source = error.source();
+        while let Some(err) = source {
+            message.push_str(": ");
+            message.push_str(&err.to_string());
+            source = err.source();
+        }
+        Self::new(path, stage, Kind::of(error), message)
+    }
+
+    pub(super) fn prefix_path(&mut self, prefix: &Path) {
+        self.path = self.path.as_ref().map(|path| prefix.join(path));
+    }
+
+    /// The path of the file, if it is known.
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// The stage of the analysis where the error occurred.
+    pub fn stage(&self) -> Stage {
+        self.stage
+    }
+
+    /// The kind of error.
+    pub fn kind(&self) -> Kind {
+        self.kind
+    }
+
+    /// A message describing the error.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(path) = &self.path {
+            write!(f, "{}: ", path.display())?;
+        }
+        write!(f, "failed to {}: {}", self.stage, self.message)
+    }
+}
+
+/// The stage of the analysis where a file was skipped.
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Stage {
+    /// Listing the files in the file source.
+    Walk,
+    /// Getting the path of a file.
+    Filepath,
+    /// Reading the contents of a file.
+    Contents,
+    /// Getting the size of a file.
+    Size,
+}
+
+impl fmt::Display for Stage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let action = match self {
+            Self::Walk => "list file",
+            Self::Filepath => "get path",
+            Self::Contents => "read contents",
+            Self::Size => "get size",
+        };
+        write!(f, "{action}")
+    }
+}
+
+/// The kind of error that caused a file to be skipped.
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Kind {
+    /// The file does not exist, such as a broken symbolic link.
+    NotFound,
+    /// The file could not be read because of its permissions.
+    PermissionDenied,
+    /// The path could not be converted to a valid path.
+    InvalidPath,
+    /// An object is missing from the repository.
+    MissingObject,
+    /// Any other error.
+    Other,
+}
+
+impl Kind {
+    /// Classifies an error by the first error in its source chain that has a
+    /// known kind.
+    pub fn of(error: &(dyn ErrorTrait + 'static)) -> Self {
+        let mut source = Some(error);
+        while let Some(err) = source {
+            if let Some(err) = err.downcast_ref::<io::Error>() {
+                return Self::of_io(err);
+            }
+            if let Some(err) = err.downcast_ref::<Error>() {
+                match err.kind() {
+                    ErrorKind::InvalidPath => return Self::InvalidPath,
+                    ErrorKind::MissingObject => return Self::MissingObject,
+                    _ => (),
+                }
+            }
+            source = err.source();
+        }
+        Self::Other
+    }
+
+    /// Classifies an I/O error.
+    pub fn of_io(error: &io::Error) -> Self {
+        match error.kind() {
+            io::ErrorKind::NotFound => Self::NotFound,
+            io::ErrorKind::PermissionDenied => Self::PermissionDenied,
+            io::ErrorKind::InvalidFilename => Self::InvalidPath,
+            _ => Self::Other,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest(
+        error,
+        expected,
+        case(Box::new(io::Error::from(io::ErrorKind::NotFound)), Kind::NotFound),
+        case(Box::new(io::Error::from(io::ErrorKind::PermissionDenied)), Kind::PermissionDenied),
+        case(
+            Box::new(Error::with_source(ErrorKind::MissingObject, io::Error::other("missing"))),
+            Kind::MissingObject
+        ),
+        case(
+            Box::new(Error::with_source(
+                ErrorKind::NoRepository,
+                io::Error::from(io::ErrorKind::NotFound)
+            )),
+            Kind::NotFound
+        ),
+        case("something else".into(), Kind::Other)
+    )]
+    fn test_kind_of(error: Box<dyn ErrorTrait>, expected: Kind) {
+        assert_eq!(Kind::of(error.as_ref()), expected);
+    }
+
+    #[test]
+    fn test_message_includes_sources() {
+        let error = Error::with_source(ErrorKind::InvalidPath, io::Error::other("not utf-8"));
+        let diagnostic = Diagnostic::from_error(None, Stage::Filepath, &error);
+        assert_eq!(diagnostic.kind(), Kind::InvalidPath);
+        assert_eq!(diagnostic.message(), "invalid path: not utf-8");
+        assert_eq!(
+            diagnostic.to_string(),
+            "failed to get path: invalid path: not utf-8"
+        );
+    }
+}
@@ -0,0 +1,205 @@
+use super::{Analysis, SummaryOpts};
+use crate::{Entry, Language};
+use indexmap::IndexMap;
+use serde::Serialize;
+use std::path::Path;
+
+/// The differences from an old analysis to a new one, by language and by
+/// file.
+///
+/// Files are compared by their size and classification, since analyses don't
+/// record the contents of files. Files that are unchanged are omitted.
+#[derive(Debug, Serialize)]
+pub struct Diff<'a> {
+    languages: Vec<LanguageDiff>,
+    files: Vec<FileDiff<'a>>,
+}
+
+impl<'a> Diff<'a> {
+    pub(super) fn new(old: &'a Analysis, new: &'a Analysis, opts: &SummaryOpts) -> Self {
+        let mut languages: IndexMap<Language, LanguageDiff> = IndexMap::new();
+        for (analysis, is_new) in [(old, false), (new, true)] {
+            for (_, entry) in analysis.iter().filter(|(_, entry)| opts.includes(entry)) {
+                let Some(&language) = entry.language() else {
+                    continue;
+                };
+                let diff = languages
+                    .entry(language)
+                    .or_insert_with(|| LanguageDiff::new(language));
+                let stats = if is_new {
+                    &mut diff.after
+                } else {
+                    &mut diff.before
+                };
+                stats.files += 1;
+                stats.size += entry.size();
+            }
+        }
+        let mut languages: Vec<_> = languages
+            .into_values()
+            .filter(|diff| diff.before != diff.after)
+            .map(LanguageDiff::with_deltas)
+            .collect();
+        languages.sort_by_key(|diff| diff.language.name());
+
+        let removed = old
+            .entries
+            .iter()
+            .filter(|(path, _)| !new.entries.contains_key(*path))
+            .filter_map(|(path, entry)| FileDiff::between(path, Some(entry), None));
+        let changed = new.entries.iter().filter_map(|(path, entry)| {
+            FileDiff::between(path, old.entries.get(path), Some(entry))
+        });
+        let mut files: Vec<_> = removed
+            .chain(changed)
+            .filter(|diff| {
+                let includes = |entry: Option<&Entry>| entry.is_some_and(|e| opts.includes(e));
+                includes(diff.before) || includes(diff.after)
+            })
+            .collect();
+        files.sort_by_key(|diff| diff.path);
+
+        Self { languages, files }
+    }
+
+    /// The languages whose file count or size changed, sorted by name.
+    pub fn languages(&self) -> &[LanguageDiff] {
+        &self.languages
+    }
+
+    /// The files that were added, removed, or changed, sorted by path.
+    pub fn files(&self) -> &[FileDiff<'a>] {
+        &self.files
+    }
+
+    /// Returns `true` if nothing changed.
+    pub fn is_empty(&self) -> bool {
+        self.languages.is_empty() && self.files.is_empty()
+    }
+}
+
+/// How the files of a language changed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[non_exhaustive]
+pub struct LanguageDiff {
+    /// The language.
+    pub language: Language,
+    /// The files of the language in the old analysis.
+    pub before: LanguageStats,
+    /// The files of the language in the new analysis.
+    pub after: LanguageStats,
+    /// The change in the number of files.
+    pub files_delta: i64,
+    /// The change in the total size of the files.
+    pub size_delta: i64,
+}
+
+impl LanguageDiff {
+    fn new(language: Language) -> Self {
+        Self {
+            language,
+            before: LanguageStats::default(),
+            after: LanguageStats::default(),
+            files_delta: 0,
+            size_delta: 0,
+        }
+    }
+
+    fn with_deltas(self) -> Self {
+        let delta = |old: usize, new: usize| new as i64 - old as i64;
+        Self {
+            files_delta: delta(self.before.files, self.after.files),
+            size_delta: delta(self.before.size, self.after.size),
+            ..self
+        }
+    }
+
+    /// Returns `true` if the language isn't in the old analysis.
+    pub fn is_added(&self) -> bool {
+        self.before.files == 0
+    }
+
+    /// Returns `true` if the language isn't in the new analysis.
+    pub fn is_removed(&self) -> bool {
+        self.after.files == 0
+    }
+}
+
+/// Statistics for a language in one analysis.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+#[non_exhaustive]
+pub struct LanguageStats {
+    /// The number of files.
+    pub files: usize,
+    /// The total size of the files.
+    pub size: usize,
+}
+
+/// How a file changed between two analyses.
+#[derive(Debug, Serialize)]
+pub struct FileDiff<'a> {
+    path: &'a Path,
+    change: Change,
+    before: Option<&'a Entry>,
+    after: Option<&'a Entry>,
+}
+
+impl<'a> FileDiff<'a> {
+    /// Compares the old and new entries of a file. Returns `None` if the
+    /// file didn't change.
+    fn between(
+        path: &'a Path,
+        before: Option<&'a Entry>,
+        after: Option<&'a Entry>,
+    ) -> Option<Self> {
+        let change = match (before, after) {
+            (None, Some(_)) => Change::Added,
+            (Some(_), None) => Change::Removed,
+            (Some(old), Some(new)) if !old.same_classification(new) => Change::Reclassified,
+            (Some(old), Some(new)) if old.size() != new.size() => Change::Changed,
+            _ => return None,
+        };
+        Some(Self {
+            path,
+            change,
+            before,
+            after,
+        })
+    }
+
+    /// The path of the file.
+    pub fn path(&self) -> &'a Path {
+        self.path
+    }
+
+    /// How the file changed.
+    pub fn change(&self) -> Change {
+        self.change
+    }
+
+    /// The file in the old analysis, unless it was added.
+    pub fn before(&self) -> Option<&'a Entry> {
+        self.before
+    }
+
+    /// The file in the new analysis, unless it was removed.
+    pub fn after(&self) -> Option<&'a Entry> {
+        self.after
+    }
+}
+
+/// How a file changed between two analyses.
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Change {
+    /// The file is only in the new analysis.
+    Added,
+    /// The file is only in the old analysis.
+    Removed,
+    /// The file's size changed, but it was classified the same way.
+    Changed,
+    /// The file's language or attributes changed, like becoming generated or
+    /// vendored.
+    Reclassified,
+}
@@ -1,27 +1,95 @@
 use super::Entry;
 use indexmap::IndexMap;
-use serde::Serialize;
+use serde::{Deserialize, Serialize, Serializer};
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
+pub use binary_summary::BinaryStats;
+pub use binary_summary::BinarySummary;
+pub use binary_summary::Iter as BinarySummaryIter;
+pub use diagnostic::Diagnostic;
+pub use diagnostic::Kind as DiagnosticKind;
+pub use diagnostic::Stage;
+pub use diff::Change;
+pub use diff::Diff;
+pub use diff::FileDiff;
+pub use diff::LanguageDiff;
+pub use diff::LanguageStats;
 pub use summary::Iter as SummaryIter;
 pub use summary::Opts as SummaryOpts;
 pub use summary::Summary;
 
+mod binary_summary;
+mod diagnostic;
+mod diff;
 mod summary;
 
-/// The result of analyzing a repository along with all of its submodules.
-#[derive(Debug, Serialize)]
-pub struct Analysis(pub(super) IndexMap<PathBuf, Entry>);
+/// The result of analyzing a file source, including any submodules that the
+/// file source was configured to analyze.
+///
+/// Analyses are serialized as their entries by path, like with
+/// `gengo --format json`, and can be deserialized again to compare them with
+/// [`Analysis::diff`]. Diagnostics aren't serialized.
+#[derive(Debug, Deserialize)]
+#[serde(from = "IndexMap<PathBuf, Entry>")]
+pub struct Analysis {
+    pub(super) entries: IndexMap<PathBuf, Entry>,
+    /// Files that were skipped because of errors.
+    pub(super) diagnostics: Vec<Diagnostic>,
+}
+
+impl Serialize for Analysis {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.entries.serialize(serializer)
+    }
+}
+
+impl From<IndexMap<PathBuf, Entry>> for Analysis {
+    fn from(entries: IndexMap<PathBuf, Entry>) -> Self {
+        Self {
+            entries,
+            diagnostics: Vec::new(),
+        }
+    }
+}
 
 impl Analysis {
     pub fn iter(&self) -> impl Iterator<Item = (&PathBuf, &Entry)> {
-        let results = &self.0;
+        let results = &self.entries;
         results.iter()
     }
 
+    /// The files that were skipped because of errors.
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    /// Joins `prefix` to each path. File sources report paths relative to their
+    /// root, so this can be used to display paths relative to the current
+    /// directory or as absolute paths.
+    pub fn prefix_paths(self, prefix: impl AsRef<Path>) -> Self {
+        let prefix = prefix.as_ref();
+        let entries = self
+            .entries
+            .into_iter()
+            .map(|(path, entry)| (prefix.join(path), entry))
+            .collect();
+        let diagnostics = self
+            .diagnostics
+            .into_iter()
+            .map(|mut diagnostic| {
+                diagnostic.prefix_path(prefix);
+                diagnostic
+            })
+            .collect();
+        Self {
+            entries,
+            diagnostics,
+        }
+    }
+
     /// Summarizes the analysis by language and size. Includes only
-    /// the entries that are detectable.
+    /// the entries that are detectable and not binary.
     pub fn summary(&self) -> Summary {
         let opts = SummaryOpts {
             all: false,
@@ -33,13 +101,40 @@ impl Analysis {
     /// Summarizes the analysis by language and size.
     pub fn summary_with(&self, opts: SummaryOpts) -> Summary {
         let mut summary = IndexMap::new();
-        for (_, entry) in self.iter() {
-            if !(opts.all || entry.detectable()) {
+        for (_, entry) in self.iter().filter(|(_, entry)| opts.includes(entry)) {
+            let Some(&language) = entry.language() else {
                 continue;
-            }
-            let language = *entry.language();
+            };
             *summary.entry(language).or_insert(0) += entry.size();
         }
         Summary(summary)
     }
+
+    /// Compares this analysis to a newer one. Like [`Analysis::summary`],
+    /// only the entries that are detectable and not binary are included.
+    pub fn diff<'a>(&'a self, new: &'a Analysis) -> Diff<'a> {
+        let opts = SummaryOpts {
+            all: false,
+            ..Default::default()
+        };
+        self.diff_with(new, opts)
+    }
+
+    /// Compares this analysis to a newer one, including the same entries as
+    /// [`Analysis::summary_with`]. Files are included if either their old or
+    /// their new entry is included.
+    pub fn diff_with<'a>(&'a self, new: &'a Analysis, opts: SummaryOpts) -> Diff<'a> {
+        Diff::new(self, new, &opts)
+    }
+
+    /// Summarizes the binary files by format, file count, and size.
+    pub fn binary_summary(&self) -> BinarySummary {
+        let mut summary: IndexMap<_, BinaryStats> = IndexMap::new();
+        for (_, entry) in self.iter().filter(|(_, entry)| entry.binary()) {
+            let stats = summary.entry(entry.binary_format()).or_default();
+            stats.files += 1;
+            stats.size += entry.size();
+        }
+        BinarySummary(summary)
+    }
 }
@@ -1,4 +1,4 @@
-use crate::Language;
+use crate::{Entry, Language};
 use indexmap::IndexMap;
 use indexmap::map::Iter as IndexMapIter;
 
@@ -43,4 +43,25 @@ impl<'map> IntoIterator for &'map Summary {
 pub struct Opts {
     /// Include all files, even if they are not detectable.
     pub all: bool,
+    /// Include binary files that have a language.
+    pub binary: bool,
+    /// Only include files that have all of these custom flags.
+    pub with_flags: Vec<String>,
+    /// Exclude files that have any of these custom flags.
+    pub without_flags: Vec<String>,
+}
+
+impl Opts {
+    /// Checks if an entry is included by these options. Entries without a
+    /// language are included, but aren't counted for any language.
+    pub(super) fn includes(&self, entry: &Entry) -> bool {
+        if !(self.all || entry.detectable()) {
+            return false;
+        }
+        if entry.binary() && !self.binary {
+            return false;
+        }
+        self.with_flags.iter().all(|flag| entry.has_flag(flag))
+            && !self.without_flags.iter().any(|flag| entry.has_flag(flag))
+    }
 }
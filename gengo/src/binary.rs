@@ -1,12 +1,97 @@
+use crate::Classifier;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::path::Path;
 
+/// A binary file format.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+#[non_exhaustive]
+pub enum BinaryFormat {
+    /// Executable and Linkable Format. Examples: Linux executables, `.so`, `.o`.
+    Elf,
+    /// Portable Executable. Examples: `.exe`, `.dll`.
+    Pe,
+    /// Mach-O. Examples: macOS executables, `.dylib`.
+    MachO,
+    /// Compiled Java class.
+    JavaClass,
+    /// PNG image.
+    Png,
+    /// JPEG image.
+    Jpeg,
+    /// GIF image.
+    Gif,
+    /// PDF document.
+    Pdf,
+    /// ZIP archive.
+    Zip,
+    /// Java archive. This is a ZIP archive with a `.jar`, `.war`, or `.ear` extension.
+    Jar,
+    /// gzip-compressed data.
+    Gzip,
+    /// WebAssembly binary.
+    Wasm,
+    /// SQLite database.
+    Sqlite,
+    /// TrueType font.
+    TrueType,
+    /// OpenType font.
+    OpenType,
+    /// Web Open Font Format.
+    Woff,
+    /// Web Open Font Format 2.
+    Woff2,
+}
+
+impl BinaryFormat {
+    /// The name of the format.
+    pub const fn name(&self) -> &'static str {
+        match self {
+            Self::Elf => "ELF",
+            Self::Pe => "PE",
+            Self::MachO => "Mach-O",
+            Self::JavaClass => "Java class",
+            Self::Png => "PNG",
+            Self::Jpeg => "JPEG",
+            Self::Gif => "GIF",
+            Self::Pdf => "PDF",
+            Self::Zip => "ZIP",
+            Self::Jar => "JAR",
+            Self::Gzip => "gzip",
+            Self::Wasm => "WebAssembly",
+            Self::Sqlite => "SQLite",
+            Self::TrueType => "TrueType",
+            Self::OpenType => "OpenType",
+            Self::Woff => "WOFF",
+            Self::Woff2 => "WOFF2",
+        }
+    }
+}
+
 pub struct Binary {
     read_limit: usize,
+    /// Maps lowercase extensions to a format, if the extension belongs to a
+    /// single known format.
+    extensions: HashMap<&'static str, Option<BinaryFormat>>,
 }
 
 impl Binary {
+    /// If more than `1 / CONTROL_RATIO_DENOMINATOR` of the characters are
+    /// control characters, the file is likely binary.
+    const CONTROL_RATIO_DENOMINATOR: usize = 10;
+    /// The number of bytes that are enough to identify a format by its magic
+    /// number.
+    pub const MAGIC_LEN: usize = 1 << 12;
+    /// The number of bytes that are checked for binary data, like git does.
+    pub const SNIFF_LEN: usize = 8000;
+
     pub fn new(read_limit: usize) -> Self {
-        Self { read_limit }
+        let extensions = Self::extensions();
+        Self {
+            read_limit,
+            extensions,
+        }
     }
 
     /// Guesses if a file is binary.
@@ -14,19 +99,264 @@ impl Binary {
         self.is_binary_no_read(&filepath) || self.is_binary_with_read(&filepath, contents)
     }
 
-    fn is_binary_no_read(&self, _filepath: impl AsRef<Path>) -> bool {
-        false
+    /// Identifies the format of a binary file. Magic numbers are checked
+    /// first, then the file's extension.
+    ///
+    /// Returns `None` if the format is unknown, even if the file is binary.
+    pub fn identify(&self, filepath: impl AsRef<Path>, contents: &[u8]) -> Option<BinaryFormat> {
+        Self::identify_by_magic(&filepath, contents)
+            .or_else(|| self.identify_by_extension(filepath))
+    }
+
+    /// The number of bytes that are needed to guess if a file is binary.
+    pub fn sniff_len(&self) -> usize {
+        Self::SNIFF_LEN.min(self.read_limit)
+    }
+
+    fn is_binary_no_read(&self, filepath: impl AsRef<Path>) -> bool {
+        Self::lowercase_extension(filepath).is_some_and(|ext| self.extensions.contains_key(&*ext))
+    }
+
+    fn is_binary_with_read(&self, filepath: impl AsRef<Path>, contents: &[u8]) -> bool {
+        let contents = &contents[..contents.len().min(self.sniff_len())];
+        Self::identify_by_magic(filepath, contents).is_some() || Self::has_control_chars(contents)
+    }
+
+    /// Identifies the format of a binary file by its extension alone.
+    pub fn identify_by_extension(&self, filepath: impl AsRef<Path>) -> Option<BinaryFormat> {
+        Self::lowercase_extension(filepath).and_then(|ext| self.extensions.get(&*ext).copied()?)
+    }
+
+    fn identify_by_magic(filepath: impl AsRef<Path>, contents: &[u8]) -> Option<BinaryFormat> {
+        use BinaryFormat::*;
+
+        let format = match contents {
+            [0x7F, b'E', b'L', b'F', ..] => Elf,
+            [b'M', b'Z', ..] if Self::has_pe_header(contents) => Pe,
+            [0xFE, 0xED, 0xFA, 0xCE | 0xCF, ..] | [0xCE | 0xCF, 0xFA, 0xED, 0xFE, ..] => MachO,
+            // NOTE Java classes and universal Mach-O binaries share a magic number.
+            //      Universal binaries store a small architecture count where Java
+            //      classes store their version, which is at least 45.
+            [0xCA, 0xFE, 0xBA, 0xBE, _, _, major, minor, ..] => {
+                if u16::from_be_bytes([*major, *minor]) >= 45 {
+                    JavaClass
+                } else {
+                    MachO
+                }
+            }
+            [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n', ..] => Png,
+            [0xFF, 0xD8, 0xFF, ..] => Jpeg,
+            [b'G', b'I', b'F', b'8', b'7' | b'9', b'a', ..] => Gif,
+            [b'%', b'P', b'D', b'F', b'-', ..] => Pdf,
+            [b'P', b'K', 0x03, 0x04, ..] | [b'P', b'K', 0x05, 0x06, ..] => {
+                let is_jar = Self::lowercase_extension(filepath)
+                    .is_some_and(|ext| matches!(&*ext, "jar" | "war" | "ear"));
+                if is_jar { Jar } else { Zip }
+            }
+            [0x1F, 0x8B, ..] => Gzip,
+            [0x00, b'a', b's', b'm', ..] => Wasm,
+            _ if contents.starts_with(b"SQLite format 3\0") => Sqlite,
+            [0x00, 0x01, 0x00, 0x00, 0x00, ..] => TrueType,
+            [b'O', b'T', b'T', b'O', 0x00, ..] => OpenType,
+            [b'w', b'O', b'F', b'F', flavor @ ..] if Self::is_font_flavor(flavor) => Woff,
+            [b'w', b'O', b'F', b'2', flavor @ ..] if Self::is_font_flavor(flavor) => Woff2,
+            _ => return None,
+        };
+        Some(format)
+    }
+
+    /// Checks that the DOS header points to a PE signature.
+    fn has_pe_header(contents: &[u8]) -> bool {
+        const PE_OFFSET_LOCATION: usize = 0x3C;
+        let Some(offset) = contents.get(PE_OFFSET_LOCATION..PE_OFFSET_LOCATION + 4) else {
+            return false;
+        };
+        let offset = u32::from_le_bytes(offset.try_into().unwrap());
+        usize::try_from(offset)
+            .ok()
+            .and_then(|offset| contents.get(offset..)?.get(..4))
+            .is_some_and(|signature| signature == b"PE\0\0")
     }
 
-    fn is_binary_with_read(&self, _filepath: impl AsRef<Path>, contents: &[u8]) -> bool {
-        // NOTE This is currently very naive, and will need to be improved as issues
-        //      are found.
+    /// Checks the "flavor" that follows a WOFF signature.
+    fn is_font_flavor(flavor: &[u8]) -> bool {
+        matches!(
+            flavor.get(..4),
+            Some([0x00, 0x01, 0x00, 0x00] | b"OTTO" | b"true")
+        )
+    }
+
+    /// Checks if a notable amount of the contents are control characters.
+    ///
+    /// UTF-16 encoded text is decoded first, so that its `NUL` bytes do not count as
+    /// control characters.
+    fn has_control_chars(contents: &[u8]) -> bool {
+        match Utf16::detect(contents) {
+            Some(encoding) => Self::exceeds_control_ratio(encoding.units(contents).map(u32::from)),
+            None => {
+                // NOTE If any of the bytes is a null byte, this is likely binary.
+                contents.contains(&0)
+                    || Self::exceeds_control_ratio(contents.iter().map(|&b| u32::from(b)))
+            }
+        }
+    }
 
-        // TODO Simply return `false` if `contents.len()` is less than the read limit?
-        //      This would be because exceptionally large files could be binary.
+    fn exceeds_control_ratio(chars: impl ExactSizeIterator<Item = u32>) -> bool {
+        let total = chars.len();
+        let control_chars = chars.filter(|&c| Self::is_control(c)).count();
+        control_chars > 0 && control_chars * Self::CONTROL_RATIO_DENOMINATOR > total
+    }
+
+    /// Control characters that wouldn't normally be in text.
+    fn is_control(c: u32) -> bool {
+        const TAB: u32 = 0x09;
+        const LINE_FEED: u32 = 0x0A;
+        const FORM_FEED: u32 = 0x0C;
+        const CARRIAGE_RETURN: u32 = 0x0D;
+        const ESCAPE: u32 = 0x1B;
+        const DELETE: u32 = 0x7F;
+
+        match c {
+            TAB | LINE_FEED | FORM_FEED | CARRIAGE_RETURN | ESCAPE => false,
+            c => c < 0x20 || c == DELETE,
+        }
+    }
+
+    fn lowercase_extension(filepath: impl AsRef<Path>) -> Option<String> {
+        filepath
+            .as_ref()
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ext.to_ascii_lowercase())
+    }
+
+    fn extensions() -> HashMap<&'static str, Option<BinaryFormat>> {
+        use BinaryFormat::*;
+
+        [
+            // Executables and libraries
+            ("a", None),
+            ("class", Some(JavaClass)),
+            ("dll", Some(Pe)),
+            ("dylib", Some(MachO)),
+            ("exe", Some(Pe)),
+            ("lib", None),
+            ("o", None),
+            ("obj", None),
+            ("pyc", None),
+            ("so", Some(Elf)),
+            ("wasm", Some(Wasm)),
+            // Archives
+            ("7z", None),
+            ("bz2", None),
+            ("ear", Some(Jar)),
+            ("gz", Some(Gzip)),
+            ("jar", Some(Jar)),
+            ("rar", None),
+            ("tgz", Some(Gzip)),
+            ("war", Some(Jar)),
+            ("xz", None),
+            ("zip", Some(Zip)),
+            // Images
+            ("bmp", None),
+            ("gif", Some(Gif)),
+            ("ico", None),
+            ("jpeg", Some(Jpeg)),
+            ("jpg", Some(Jpeg)),
+            ("png", Some(Png)),
+            ("psd", None),
+            ("tif", None),
+            ("tiff", None),
+            ("webp", None),
+            // Audio and video
+            ("avi", None),
+            ("flac", None),
+            ("mkv", None),
+            ("mov", None),
+            ("mp3", None),
+            ("mp4", None),
+            ("ogg", None),
+            ("wav", None),
+            ("webm", None),
+            // Documents
+            ("pdf", Some(Pdf)),
+            // Databases
+            ("sqlite", Some(Sqlite)),
+            ("sqlite3", Some(Sqlite)),
+            // Fonts
+            ("eot", None),
+            ("otf", Some(OpenType)),
+            ("ttf", Some(TrueType)),
+            ("woff", Some(Woff)),
+            ("woff2", Some(Woff2)),
+        ]
+        .into_iter()
+        .collect()
+    }
+}
+
+impl Classifier for Binary {
+    fn classify_no_read(&self, filepath: &Path) -> Option<bool> {
+        self.is_binary_no_read(filepath).then_some(true)
+    }
 
-        // NOTE If any of the bytes is a null byte, this is likely binary.
-        contents.iter().take(self.read_limit).any(|&b| b == 0)
+    fn classify(&self, filepath: &Path, contents: &[u8]) -> bool {
+        self.is_binary(filepath, contents)
+    }
+}
+
+/// UTF-16 byte orders.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Utf16 {
+    LittleEndian,
+    BigEndian,
+}
+
+impl Utf16 {
+    /// Detects UTF-16 by its byte order mark, or by `NUL` bytes consistently
+    /// being in only the even or odd positions, which is common for UTF-16
+    /// text made of mostly ASCII characters.
+    fn detect(contents: &[u8]) -> Option<Self> {
+        match contents {
+            [0xFF, 0xFE, ..] => return Some(Self::LittleEndian),
+            [0xFE, 0xFF, ..] => return Some(Self::BigEndian),
+            _ => {}
+        }
+
+        if contents.len() < 4 || !contents.len().is_multiple_of(2) {
+            return None;
+        }
+        let pairs = contents.len() / 2;
+        let (even_nuls, odd_nuls) = contents.chunks_exact(2).fold((0, 0), |(even, odd), pair| {
+            (
+                even + usize::from(pair[0] == 0),
+                odd + usize::from(pair[1] == 0),
+            )
+        });
+
+        // NOTE Allow some non-ASCII characters, but require that most of the
+        //      text is ASCII and that NULs appear only in one position.
+        let is_mostly = |nuls: usize| nuls * 4 >= pairs * 3;
+        match (even_nuls, odd_nuls) {
+            (0, odd) if is_mostly(odd) => Some(Self::LittleEndian),
+            (even, 0) if is_mostly(even) => Some(Self::BigEndian),
+            _ => None,
+        }
+    }
+
+    /// Iterates over the code units, skipping the byte order mark.
+    fn units<'a>(self, contents: &'a [u8]) -> impl ExactSizeIterator<Item = u16> + 'a {
+        let contents = match contents {
+            [0xFF, 0xFE, rest @ ..] | [0xFE, 0xFF, rest @ ..] => rest,
+            contents => contents,
+        };
+        contents.chunks_exact(2).map(move |pair| {
+            let pair = [pair[0], pair[1]];
+            match self {
+                Self::LittleEndian => u16::from_le_bytes(pair),
+                Self::BigEndian => u16::from_be_bytes(pair),
+            }
+        })
     }
 }
 
@@ -41,7 +371,9 @@ mod tests {
         expected,
         case("foo.txt", "", false),
         case("foo.txt", "Hello, world!", false),
-        case("foo.txt", "ab\0c", true)
+        case("foo.txt", "ab\0c", true),
+        case("foo.txt", "\x01\x02\x03\x04 hello", true),
+        case("foo.txt", "\x1b[31mred\x1b[0m\n\tindented\x0c\r\n", false)
     )]
     fn test_is_binary_with_read(filepath: &str, contents: &str, expected: bool) {
         let binary = Binary::new(1 << 20);
@@ -50,4 +382,73 @@ mod tests {
             expected
         );
     }
+
+    #[rstest(
+        contents,
+        case::little_endian_bom(b"\xFF\xFEH\0e\0l\0l\0o\0\n\0"),
+        case::big_endian_bom(b"\xFE\xFF\0H\0e\0l\0l\0o\0\n"),
+        case::little_endian(b"H\0e\0l\0l\0o\0,\0 \0w\0o\0r\0l\0d\0"),
+        case::big_endian(b"\0H\0e\0l\0l\0o\0,\0 \0w\0o\0r\0l\0d")
+    )]
+    fn test_utf16_is_not_binary(contents: &[u8]) {
+        let binary = Binary::new(1 << 20);
+        assert!(!binary.is_binary_with_read("foo.txt", contents));
+    }
+
+    #[rstest(
+        filepath,
+        expected,
+        case("image.png", true),
+        case("image.PNG", true),
+        case("lib/app.jar", true),
+        case("fonts/font.woff2", true),
+        case("src/main.rs", false),
+        case("image.svg", false),
+        case("Makefile", false)
+    )]
+    fn test_is_binary_no_read(filepath: &str, expected: bool) {
+        let binary = Binary::new(1 << 20);
+        assert_eq!(binary.is_binary_no_read(filepath), expected);
+    }
+
+    #[rstest(
+        filepath,
+        contents,
+        expected,
+        case("a.out", b"\x7FELF\x02\x01\x01\0", Some(BinaryFormat::Elf)),
+        case("a.out", b"\xCF\xFA\xED\xFE\x07\0\0\x01", Some(BinaryFormat::MachO)),
+        case("a.out", b"\xCA\xFE\xBA\xBE\0\0\0\x02", Some(BinaryFormat::MachO)),
+        case("Main", b"\xCA\xFE\xBA\xBE\0\0\0\x41", Some(BinaryFormat::JavaClass)),
+        case("image", b"\x89PNG\r\n\x1A\n\0\0\0\x0DIHDR", Some(BinaryFormat::Png)),
+        case("image", b"\xFF\xD8\xFF\xE0\0\x10JFIF", Some(BinaryFormat::Jpeg)),
+        case("image", b"GIF89a\x01\0\x01\0", Some(BinaryFormat::Gif)),
+        case("doc", b"%PDF-1.7\n", Some(BinaryFormat::Pdf)),
+        case("archive", b"PK\x03\x04\x14\0", Some(BinaryFormat::Zip)),
+        case("app.jar", b"PK\x03\x04\x14\0", Some(BinaryFormat::Jar)),
+        case("archive", b"\x1F\x8B\x08\0", Some(BinaryFormat::Gzip)),
+        case("module", b"\0asm\x01\0\0\0", Some(BinaryFormat::Wasm)),
+        case("db", b"SQLite format 3\0\x10\0", Some(BinaryFormat::Sqlite)),
+        case("font", b"\0\x01\0\0\0\x0F\0\x80", Some(BinaryFormat::TrueType)),
+        case("font", b"OTTO\0\x0C\0\x80", Some(BinaryFormat::OpenType)),
+        case("font", b"wOFF\0\x01\0\0", Some(BinaryFormat::Woff)),
+        case("font", b"wOF2OTTO", Some(BinaryFormat::Woff2)),
+        case("image.png", b"not actually a png", Some(BinaryFormat::Png)),
+        case("notes.txt", b"MZ is not an executable", None),
+        case("notes.txt", b"OTTO is a name", None),
+        case("notes.txt", b"Hello, world!", None)
+    )]
+    fn test_identify(filepath: &str, contents: &[u8], expected: Option<BinaryFormat>) {
+        let binary = Binary::new(1 << 20);
+        assert_eq!(binary.identify(filepath, contents), expected);
+    }
+
+    #[test]
+    fn test_identify_pe() {
+        let mut contents = vec![0; 0x80];
+        contents[..2].copy_from_slice(b"MZ");
+        contents[0x3C..0x40].copy_from_slice(&0x40u32.to_le_bytes());
+        contents[0x40..0x44].copy_from_slice(b"PE\0\0");
+        let binary = Binary::new(1 << 20);
+        assert_eq!(binary.identify("app", &contents), Some(BinaryFormat::Pe));
+    }
 }
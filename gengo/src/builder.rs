@@ -1,16 +1,25 @@
 use super::Gengo;
 use super::binary::Binary;
+use super::classifier::Classifier;
+use super::config::Config;
+use super::detectability::DetectabilityPolicy;
 use super::documentation::Documentation;
-use super::generated::Generated;
+use super::generated::{Generated, MinificationThresholds};
+use super::overrides::OverrideRules;
 use super::vendored::Vendored;
 
-use crate::file_source::FileSource;
+use crate::file_source::{FileSource, Overrides};
 use std::error::Error as ErrorTrait;
 
 /// Builds a new `Gengo` instance.
 pub struct Builder<FS: for<'fs> FileSource<'fs>> {
     file_source: FS,
     read_limit: Option<usize>,
+    minification: MinificationThresholds,
+    classifiers: Vec<(String, Box<dyn Classifier>)>,
+    override_rules: Vec<(String, Overrides)>,
+    detectability: Option<DetectabilityPolicy>,
+    read_config: bool,
 }
 
 impl<FS: for<'fs> FileSource<'fs>> Builder<FS> {
@@ -20,24 +29,106 @@ impl<FS: for<'fs> FileSource<'fs>> Builder<FS> {
         Self {
             file_source,
             read_limit: None,
+            minification: Default::default(),
+            classifiers: Vec::new(),
+            override_rules: Vec::new(),
+            detectability: None,
+            read_config: true,
         }
     }
 
     /// Sets the limit for how many bytes should be read from each file for
-    /// heuristic analysis. If this is not set, `DEFAULT_READ_LIMIT` will be
-    /// used.
+    /// heuristic analysis. If this is not set, the `read-limit` from the
+    /// configuration file will be used, falling back to `DEFAULT_READ_LIMIT`.
     pub fn read_limit(mut self, read_limit: usize) -> Self {
         self.read_limit = Some(read_limit);
         self
     }
 
+    /// Sets the thresholds used to detect minified files and bundles, which
+    /// are considered generated.
+    pub fn minification(mut self, thresholds: MinificationThresholds) -> Self {
+        self.minification = thresholds;
+        self
+    }
+
+    /// Registers a custom classifier. Files that it classifies will have a
+    /// flag with the given name.
+    pub fn classifier(
+        mut self,
+        name: impl Into<String>,
+        classifier: impl Classifier + 'static,
+    ) -> Self {
+        self.classifiers.push((name.into(), Box::new(classifier)));
+        self
+    }
+
+    /// Adds a rule that overrides the detected attributes of files matching
+    /// `glob`. Globs are matched against the path relative to the root of the
+    /// file source, where `*` does not match `/` and `**` matches any number of
+    /// directories.
+    ///
+    /// Rules are applied in the order they are added, so later rules take
+    /// precedence. Like `$GIT_DIR/info/attributes`, these rules take precedence
+    /// over any overrides provided by the file source, such as
+    /// `.gitattributes`.
+    pub fn override_rule(mut self, glob: impl Into<String>, overrides: Overrides) -> Self {
+        self.override_rules.push((glob.into(), overrides));
+        self
+    }
+
+    /// Adds multiple override rules. See [`Builder::override_rule`].
+    pub fn override_rules<S: Into<String>>(
+        mut self,
+        rules: impl IntoIterator<Item = (S, Overrides)>,
+    ) -> Self {
+        self.override_rules.extend(
+            rules
+                .into_iter()
+                .map(|(glob, overrides)| (glob.into(), overrides)),
+        );
+        self
+    }
+
+    /// Sets the policy that decides which languages are detectable. This
+    /// replaces the policy from the configuration file.
+    pub fn detectability(mut self, policy: DetectabilityPolicy) -> Self {
+        self.detectability = Some(policy);
+        self
+    }
+
+    /// Sets if the `.gengo.toml` configuration file should be read from the
+    /// root of the file source. Defaults to `true`.
+    ///
+    /// Options set on the builder take precedence over the configuration file,
+    /// and override rules from the builder are applied after override rules
+    /// from the configuration file.
+    pub fn read_config(mut self, read_config: bool) -> Self {
+        self.read_config = read_config;
+        self
+    }
+
     pub fn build(self) -> Result<Gengo<FS>, Box<dyn ErrorTrait>> {
         let file_source = self.file_source;
-        let read_limit = self.read_limit.unwrap_or(Self::DEFAULT_READ_LIMIT);
+        let config = if self.read_config {
+            Config::read(&file_source)?
+        } else {
+            Default::default()
+        };
+        let read_limit = self
+            .read_limit
+            .or(config.read_limit)
+            .unwrap_or(Self::DEFAULT_READ_LIMIT);
         let binary = Binary::new(read_limit);
-        let documentation = Documentation::new();
-        let generated = Generated::new();
-        let vendored = Vendored::new();
+        let documentation = Documentation::new().with_globs(config.documentation);
+        let generated = Generated::new(self.minification).with_globs(config.generated);
+        let vendored = Vendored::new().with_globs(config.vendored);
+        let override_rules =
+            OverrideRules::new(config.override_rules.into_iter().chain(self.override_rules))?;
+        let detectability = self
+            .detectability
+            .or(config.detectability)
+            .unwrap_or_default();
         Ok(Gengo {
             file_source,
             read_limit,
@@ -45,6 +136,10 @@ impl<FS: for<'fs> FileSource<'fs>> Builder<FS> {
             documentation,
             generated,
             vendored,
+            classifiers: self.classifiers,
+            override_rules,
+            detectability,
+            ignore: config.ignore,
         })
     }
 }
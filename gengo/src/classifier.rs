@@ -0,0 +1,46 @@
+use std::path::Path;
+
+/// Classifies files, flagging them by their paths and contents.
+///
+/// The built-in checks for binary, documentation, generated, and vendored
+/// files are classifiers. Custom classifiers can be registered with
+/// [`Builder::classifier`](crate::Builder::classifier) to add named flags to
+/// each [`Entry`](crate::Entry).
+///
+/// # Example
+///
+/// ```
+/// use gengo::Classifier;
+/// use std::path::Path;
+///
+/// struct Fixture;
+///
+/// impl Classifier for Fixture {
+///     fn classify_no_read(&self, filepath: &Path) -> Option<bool> {
+///         Some(filepath.starts_with("fixtures"))
+///     }
+///
+///     fn classify(&self, filepath: &Path, _contents: &[u8]) -> bool {
+///         filepath.starts_with("fixtures")
+///     }
+/// }
+/// ```
+pub trait Classifier: Send + Sync {
+    /// Classifies a file by its path alone. Returns `None` if the contents are
+    /// needed to decide.
+    ///
+    /// For performance reasons, this is checked before reading the contents.
+    fn classify_no_read(&self, _filepath: &Path) -> Option<bool> {
+        None
+    }
+
+    /// Classifies a file by its path and contents.
+    fn classify(&self, filepath: &Path, contents: &[u8]) -> bool;
+}
+
+/// Runs a classifier, only checking the contents if the filepath isn't enough.
+pub(crate) fn run(classifier: &dyn Classifier, filepath: &Path, contents: &[u8]) -> bool {
+    classifier
+        .classify_no_read(filepath)
+        .unwrap_or_else(|| classifier.classify(filepath, contents))
+}
@@ -0,0 +1,203 @@
+//! Repository-level configuration read from a `.gengo.toml` file.
+use super::GLOB_MATCH_OPTIONS;
+use crate::file_source::FileSource;
+use crate::language::Category;
+use crate::{DetectabilityPolicy, Error, ErrorKind, Language, Overrides};
+use glob::Pattern;
+use indexmap::IndexMap;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// The configuration from the root of the file source.
+#[derive(Default)]
+pub struct Config {
+    pub read_limit: Option<usize>,
+    pub ignore: Vec<Pattern>,
+    pub generated: Vec<Pattern>,
+    pub vendored: Vec<Pattern>,
+    pub documentation: Vec<Pattern>,
+    pub detectability: Option<DetectabilityPolicy>,
+    pub override_rules: Vec<(String, Overrides)>,
+}
+
+impl Config {
+    /// The name of the configuration file.
+    pub const FILENAME: &'static str = ".gengo.toml";
+
+    /// Reads the configuration file from the root of the file source. Returns the
+    /// default configuration if the file does not exist.
+    pub fn read<FS: for<'fs> FileSource<'fs>>(file_source: &FS) -> crate::Result<Self> {
+        let Some(contents) = file_source.read_root_file(Path::new(Self::FILENAME))? else {
+            return Ok(Default::default());
+        };
+        let contents = String::from_utf8(contents)
+            .map_err(|err| Error::with_source(ErrorKind::InvalidConfig, err))?;
+        Self::parse(&contents)
+    }
+
+    fn parse(contents: &str) -> crate::Result<Self> {
+        let raw: RawConfig = toml::from_str(contents)
+            .map_err(|err| Error::with_source(ErrorKind::InvalidConfig, err))?;
+        raw.resolve()
+    }
+}
+
+/// The configuration file as it is written.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+struct RawConfig {
+    read_limit: Option<usize>,
+    ignore: Vec<String>,
+    globs: RawGlobs,
+    detectable: IndexMap<String, bool>,
+    aliases: HashMap<String, String>,
+    #[serde(rename = "override")]
+    overrides: Vec<RawOverrideRule>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RawGlobs {
+    generated: Vec<String>,
+    vendored: Vec<String>,
+    documentation: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawOverrideRule {
+    glob: String,
+    language: Option<String>,
+    documentation: Option<bool>,
+    generated: Option<bool>,
+    vendored: Option<bool>,
+    detectable: Option<bool>,
+    binary: Option<bool>,
+}
+
+impl RawConfig {
+    fn resolve(self) -> crate::Result<Config> {
+        let language = |name: &str| -> crate::Result<Language> {
+            let name = self.aliases.get(name).map_or(name, String::as_str);
+            name.parse()
+                .map_err(|_| format!("unknown language {name:?} in {}", Config::FILENAME).into())
+        };
+
+        let detectability = if self.detectable.is_empty() {
+            None
+        } else {
+            let mut policy = DetectabilityPolicy::default();
+            for (name, &detectable) in self.detectable.iter() {
+                policy = match name.parse::<Category>() {
+                    Ok(category) => policy.category(category, detectable),
+                    Err(_) => policy.language(language(name)?, detectable),
+                };
+            }
+            Some(policy)
+        };
+
+        let override_rules = self
+            .overrides
+            .iter()
+            .map(|rule| {
+                let overrides = Overrides {
+                    language: rule.language.as_deref().map(language).transpose()?,
+                    is_documentation: rule.documentation,
+                    is_generated: rule.generated,
+                    is_vendored: rule.vendored,
+                    is_detectable: rule.detectable,
+                    is_binary: rule.binary,
+                };
+                // NOTE Validate early so that errors point to the config file.
+                Pattern::new(&rule.glob)?;
+                Ok((rule.glob.clone(), overrides))
+            })
+            .collect::<crate::Result<_>>()?;
+
+        Ok(Config {
+            read_limit: self.read_limit,
+            ignore: Self::patterns(&self.ignore)?,
+            generated: Self::patterns(&self.globs.generated)?,
+            vendored: Self::patterns(&self.globs.vendored)?,
+            documentation: Self::patterns(&self.globs.documentation)?,
+            detectability,
+            override_rules,
+        })
+    }
+
+    fn patterns(globs: &[String]) -> crate::Result<Vec<Pattern>> {
+        globs.iter().map(|glob| Ok(Pattern::new(glob)?)).collect()
+    }
+}
+
+/// Checks if a filepath matches any of the patterns.
+pub fn matches_any(patterns: &[Pattern], filepath: impl AsRef<Path>) -> bool {
+    patterns
+        .iter()
+        .any(|p| p.matches_path_with(filepath.as_ref(), GLOB_MATCH_OPTIONS))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let config = Config::parse(
+            r#"
+            read-limit = 4096
+            ignore = ["fixtures/**"]
+
+            [globs]
+            generated = ["build/**"]
+            vendored = ["third_party/**"]
+
+            [detectable]
+            data = true
+            Json = false
+            ts = true
+
+            [aliases]
+            ts = "Typescript"
+
+            [[override]]
+            glob = "dist/**"
+            language = "ts"
+            generated = false
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.read_limit, Some(4096));
+        assert!(matches_any(&config.ignore, "fixtures/a/b.json"));
+        assert!(matches_any(&config.generated, "build/out.js"));
+        assert!(matches_any(&config.vendored, "third_party/lib.c"));
+        assert!(config.documentation.is_empty());
+
+        let policy = config.detectability.unwrap();
+        assert!(policy.is_language_detectable(&Language::Yaml));
+        assert!(!policy.is_language_detectable(&Language::Json));
+        assert!(policy.is_language_detectable(&Language::Typescript));
+
+        let (glob, overrides) = &config.override_rules[0];
+        assert_eq!(glob, "dist/**");
+        assert_eq!(overrides.language, Some(Language::Typescript));
+        assert_eq!(overrides.is_generated, Some(false));
+        assert_eq!(overrides.is_vendored, None);
+    }
+
+    #[test]
+    fn test_empty() {
+        let config = Config::parse("").unwrap();
+        assert!(config.read_limit.is_none());
+        assert!(config.detectability.is_none());
+        assert!(config.override_rules.is_empty());
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!(Config::parse("unknown-key = 1").is_err());
+        assert!(Config::parse("[detectable]\nNotALanguage = true").is_err());
+        assert!(Config::parse("[[override]]\nglob = \"*\"\nlanguage = \"Nope\"").is_err());
+    }
+}
@@ -0,0 +1,126 @@
+use crate::file_source::FileSource;
+
+/// Lazily reads a file's contents, so that only as many bytes as the analysis
+/// needs are read.
+pub struct LazyContents<'a, 'fs, FS: FileSource<'fs>> {
+    file_source: &'fs FS,
+    entry: &'a FS::Entry,
+    state: &'a mut FS::State,
+    /// The real size of the file, if the file source knows it.
+    size: Option<u64>,
+    /// The bytes that were read from the start of the file.
+    head: Vec<u8>,
+    /// The number of bytes that were requested from the start of the file.
+    requested: usize,
+    /// The bytes that were read from the end of the file, if they weren't
+    /// read with the start.
+    tail: Vec<u8>,
+    /// If the contents can be read at all.
+    available: bool,
+}
+
+impl<'a, 'fs, FS: FileSource<'fs>> LazyContents<'a, 'fs, FS> {
+    pub fn new(
+        file_source: &'fs FS,
+        entry: &'a FS::Entry,
+        state: &'a mut FS::State,
+        size: Option<u64>,
+    ) -> Self {
+        Self {
+            file_source,
+            entry,
+            state,
+            size,
+            head: Vec::new(),
+            requested: 0,
+            tail: Vec::new(),
+            available: true,
+        }
+    }
+
+    /// Marks the contents as unavailable, so that they are always empty.
+    pub fn unavailable(mut self) -> Self {
+        self.available = false;
+        self
+    }
+
+    /// Gets at most `len` bytes from the start of the file. If more bytes are
+    /// needed than were previously read, only the bytes after them are read.
+    pub fn get(&mut self, len: usize) -> crate::Result<&[u8]> {
+        if !self.available {
+            return Ok(&[]);
+        }
+        if len > self.requested && !self.is_complete() {
+            let offset = self.head.len();
+            let mut rest =
+                self.file_source
+                    .contents_range(self.entry, self.state, offset, len - offset)?;
+            self.head.append(&mut rest);
+            self.requested = len;
+        }
+        Ok(&self.head[..len.min(self.head.len())])
+    }
+
+    /// Gets at most `len` bytes from the end of the file. Bytes that were
+    /// already read from the start or the end aren't read again. If the file
+    /// source doesn't know the size of the file, the end of what was read
+    /// from the start is used.
+    pub fn tail(&mut self, len: usize) -> crate::Result<&[u8]> {
+        if !self.available {
+            return Ok(&[]);
+        }
+        let Some(size) = self.size else {
+            return Ok(&self.head[self.head.len().saturating_sub(len)..]);
+        };
+        let size = usize::try_from(size).unwrap_or(usize::MAX);
+        let start = size.saturating_sub(len);
+        // NOTE The bytes before the end that was already read.
+        let end = size - self.tail.len();
+        if start <= self.head.len() {
+            // NOTE The start and the end meet, so the rest of the file, which
+            //      is at most `len` bytes, is added to the start.
+            if !self.is_complete() {
+                let offset = self.head.len();
+                if offset < end {
+                    let mut rest = self.file_source.contents_range(
+                        self.entry,
+                        self.state,
+                        offset,
+                        end - offset,
+                    )?;
+                    self.head.append(&mut rest);
+                }
+                let overlap = offset.saturating_sub(end).min(self.tail.len());
+                self.head.extend_from_slice(&self.tail[overlap..]);
+                self.tail.clear();
+                self.requested = size;
+            }
+            return Ok(&self.head[start.min(self.head.len())..]);
+        }
+        if start < end {
+            let mut tail =
+                self.file_source
+                    .contents_range(self.entry, self.state, start, end - start)?;
+            tail.append(&mut self.tail);
+            self.tail = tail;
+        }
+        Ok(&self.tail[self.tail.len().saturating_sub(len)..])
+    }
+
+    /// Checks if the contents have been read to the end of the file.
+    fn is_complete(&self) -> bool {
+        let len = self.head.len();
+        // NOTE Reading less than requested means that the end was reached.
+        len < self.requested || self.size.is_some_and(|size| len as u64 >= size)
+    }
+
+    /// Checks if `limit` bytes were needed, but the file is larger than what
+    /// was read. Unavailable contents are always truncated, unless the file is
+    /// empty.
+    pub fn truncated(&self, size: usize, limit: usize) -> bool {
+        if !self.available {
+            return size > 0;
+        }
+        self.requested >= limit && size > self.head.len()
+    }
+}
@@ -0,0 +1,123 @@
+use crate::Language;
+use crate::language::Category;
+use std::collections::HashMap;
+
+/// Decides which languages are detectable, meaning that they are included in
+/// language statistics by default.
+///
+/// Rules for a language take precedence over rules for its category. Languages
+/// with no matching rules are detectable. Regardless of these rules, generated,
+/// documentation, and vendored files are not detectable.
+///
+/// By default, data and prose are not detectable.
+///
+/// # Example
+///
+/// ```
+/// use gengo::{DetectabilityPolicy, Language};
+/// use gengo::language::Category;
+///
+/// // Count SQL and Markdown, but ignore Makefiles.
+/// let policy = DetectabilityPolicy::default()
+///     .language(Language::Sql, true)
+///     .language(Language::Markdown, true)
+///     .language(Language::Makefile, false);
+/// assert!(policy.is_language_detectable(&Language::Sql));
+/// assert!(!policy.is_language_detectable(&Language::Json));
+/// ```
+#[derive(Clone, Debug)]
+pub struct DetectabilityPolicy {
+    categories: HashMap<Category, bool>,
+    languages: HashMap<Language, bool>,
+}
+
+impl DetectabilityPolicy {
+    /// Creates a policy where every language is detectable.
+    pub fn all() -> Self {
+        Self {
+            categories: HashMap::new(),
+            languages: HashMap::new(),
+        }
+    }
+
+    /// Sets if languages in a category are detectable.
+    pub fn category(mut self, category: Category, detectable: bool) -> Self {
+        self.categories.insert(category, detectable);
+        self
+    }
+
+    /// Sets if a language is detectable. This takes precedence over the
+    /// language's category.
+    pub fn language(mut self, language: Language, detectable: bool) -> Self {
+        self.languages.insert(language, detectable);
+        self
+    }
+
+    /// Checks if a language is detectable.
+    pub fn is_language_detectable(&self, language: &Language) -> bool {
+        self.languages
+            .get(language)
+            .or_else(|| self.categories.get(&language.category()))
+            .copied()
+            .unwrap_or(true)
+    }
+
+    /// Checks if a file is detectable.
+    pub(crate) fn is_detectable(
+        &self,
+        language: &Language,
+        generated: bool,
+        documentation: bool,
+        vendored: bool,
+    ) -> bool {
+        self.is_language_detectable(language) && !(generated || documentation || vendored)
+    }
+}
+
+impl Default for DetectabilityPolicy {
+    fn default() -> Self {
+        Self::all()
+            .category(Category::Data, false)
+            .category(Category::Prose, false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest(
+        language,
+        expected,
+        case(Language::Rust, true),
+        case(Language::Json, false),
+        case(Language::Markdown, false),
+        case(Language::Sql, true)
+    )]
+    fn test_default_policy(language: Language, expected: bool) {
+        let policy = DetectabilityPolicy::default();
+        assert_eq!(policy.is_language_detectable(&language), expected);
+    }
+
+    #[test]
+    fn test_language_rules_take_precedence() {
+        let policy = DetectabilityPolicy::default()
+            .category(Category::Programming, false)
+            .language(Language::Rust, true)
+            .language(Language::Markdown, true);
+        assert!(policy.is_language_detectable(&Language::Rust));
+        assert!(policy.is_language_detectable(&Language::Markdown));
+        assert!(!policy.is_language_detectable(&Language::Python));
+        assert!(!policy.is_language_detectable(&Language::PlainText));
+    }
+
+    #[test]
+    fn test_flags_are_never_detectable() {
+        let policy = DetectabilityPolicy::all();
+        assert!(policy.is_detectable(&Language::Rust, false, false, false));
+        assert!(!policy.is_detectable(&Language::Rust, true, false, false));
+        assert!(!policy.is_detectable(&Language::Rust, false, true, false));
+        assert!(!policy.is_detectable(&Language::Rust, false, false, true));
+    }
+}
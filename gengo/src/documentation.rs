@@ -1,4 +1,5 @@
 use super::GLOB_MATCH_OPTIONS;
+use crate::Classifier;
 use glob::Pattern;
 use std::path::Path;
 
@@ -13,9 +14,10 @@ impl Documentation {
         Self { globs }
     }
 
-    pub fn is_documentation(&self, filepath: impl AsRef<Path>, contents: &[u8]) -> bool {
-        self.is_documentation_no_read(&filepath)
-            || self.is_documentation_with_read(&filepath, contents)
+    /// Adds globs that match files in addition to the built-in globs.
+    pub fn with_globs(mut self, globs: impl IntoIterator<Item = Pattern>) -> Self {
+        self.globs.extend(globs);
+        self
     }
 
     fn is_documentation_no_read(&self, filepath: impl AsRef<Path>) -> bool {
@@ -24,10 +26,6 @@ impl Documentation {
             .any(|g| g.matches_path_with(filepath.as_ref(), GLOB_MATCH_OPTIONS))
     }
 
-    fn is_documentation_with_read(&self, _filepath: impl AsRef<Path>, _contents: &[u8]) -> bool {
-        false
-    }
-
     fn globs() -> Vec<Pattern> {
         [
             // Directories
@@ -46,6 +44,17 @@ impl Documentation {
     }
 }
 
+impl Classifier for Documentation {
+    // NOTE Documentation is currently only detected by the filepath.
+    fn classify_no_read(&self, filepath: &Path) -> Option<bool> {
+        Some(self.is_documentation_no_read(filepath))
+    }
+
+    fn classify(&self, filepath: &Path, _contents: &[u8]) -> bool {
+        self.is_documentation_no_read(filepath)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -24,7 +24,16 @@ macro_rules! error_kind {
     };
 }
 
-error_kind!(NoRepository, "no repository found");
+error_kind!(
+    NoRepository,
+    "no repository found",
+    InvalidConfig,
+    "invalid configuration file",
+    InvalidPath,
+    "invalid path",
+    MissingObject,
+    "missing object"
+);
 
 impl ErrorTrait for ErrorKind {}
 
@@ -0,0 +1,429 @@
+//! A minimal `.gitattributes` evaluator for file sources that aren't backed by
+//! a git index.
+use super::{
+    BINARY_ATTR, DETECTABLE_ATTR, DOCUMENTATION_ATTR, GENERATED_ATTR, LANGUAGE_ATTR, Overrides,
+    VENDORED_ATTR,
+};
+use crate::GLOB_MATCH_OPTIONS;
+use glob::Pattern;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, PoisonError, RwLock};
+
+/// The maximum depth of nested macro expansions, to avoid infinite recursion.
+const MAX_MACRO_DEPTH: usize = 16;
+
+/// The state of a single attribute.
+#[derive(Clone, Debug, PartialEq)]
+enum State {
+    /// `attr`
+    Set,
+    /// `-attr`
+    Unset,
+    /// `!attr`
+    Unspecified,
+    /// `attr=value`
+    Value(String),
+}
+
+impl State {
+    /// Converts the state to a boolean, following git's rule that values
+    /// count as set.
+    fn as_bool(&self) -> Option<bool> {
+        match self {
+            Self::Set | Self::Value(_) => Some(true),
+            Self::Unset => Some(false),
+            Self::Unspecified => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Assignment {
+    name: String,
+    state: State,
+}
+
+impl Assignment {
+    fn parse(token: &str) -> Option<Self> {
+        let (name, state) = if let Some(name) = token.strip_prefix('-') {
+            (name, State::Unset)
+        } else if let Some(name) = token.strip_prefix('!') {
+            (name, State::Unspecified)
+        } else if let Some((name, value)) = token.split_once('=') {
+            (name, State::Value(value.to_owned()))
+        } else {
+            (token, State::Set)
+        };
+        (!name.is_empty()).then(|| Self {
+            name: name.to_owned(),
+            state,
+        })
+    }
+}
+
+/// A line that assigns attributes to matching paths.
+#[derive(Debug)]
+struct Rule {
+    pattern: Pattern,
+    /// Patterns without a `/` match the file name at any depth.
+    basename_only: bool,
+    assignments: Vec<Assignment>,
+}
+
+impl Rule {
+    /// Checks if the rule matches `path`, which is relative to the directory
+    /// containing the attributes file.
+    fn matches(&self, path: &Path) -> bool {
+        if self.basename_only {
+            path.file_name().is_some_and(|name| {
+                self.pattern
+                    .matches_with(&name.to_string_lossy(), GLOB_MATCH_OPTIONS)
+            })
+        } else {
+            self.pattern.matches_path_with(path, GLOB_MATCH_OPTIONS)
+        }
+    }
+}
+
+/// A parsed attributes file.
+#[derive(Debug, Default)]
+struct AttributesFile {
+    rules: Vec<Rule>,
+    macros: Vec<(String, Vec<Assignment>)>,
+}
+
+impl AttributesFile {
+    /// Parses the contents of an attributes file. Lines that can't be parsed,
+    /// like directory patterns or invalid globs, are skipped like git would.
+    fn parse(contents: &str) -> Self {
+        let mut file = Self::default();
+        for line in contents.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut tokens = line.split_ascii_whitespace();
+            let Some(pattern) = tokens.next() else {
+                continue;
+            };
+            let assignments = tokens.filter_map(Assignment::parse).collect();
+
+            if let Some(name) = pattern.strip_prefix("[attr]") {
+                file.macros.push((name.to_owned(), assignments));
+                continue;
+            }
+            // NOTE Negative patterns are forbidden, and directory patterns never
+            //      match files.
+            if pattern.starts_with('!') || pattern.ends_with('/') {
+                continue;
+            }
+            let basename_only = !pattern.contains('/');
+            let pattern = pattern.strip_prefix('/').unwrap_or(pattern);
+            let Ok(pattern) = Pattern::new(pattern) else {
+                continue;
+            };
+            file.rules.push(Rule {
+                pattern,
+                basename_only,
+                assignments,
+            });
+        }
+        file
+    }
+}
+
+/// Resolved attribute states, applied in order of increasing precedence.
+struct Resolved<'a> {
+    macros: &'a HashMap<String, Vec<Assignment>>,
+    states: HashMap<&'a str, &'a State>,
+}
+
+impl<'a> Resolved<'a> {
+    fn new(macros: &'a HashMap<String, Vec<Assignment>>) -> Self {
+        Self {
+            macros,
+            states: HashMap::new(),
+        }
+    }
+
+    /// Applies each rule of `file` that matches `path`, which is relative to
+    /// the directory containing the file.
+    fn apply_file(&mut self, file: &'a AttributesFile, path: &Path) {
+        file.rules
+            .iter()
+            .filter(|rule| rule.matches(path))
+            .for_each(|rule| self.apply(&rule.assignments, 0));
+    }
+
+    fn apply(&mut self, assignments: &'a [Assignment], depth: usize) {
+        for assignment in assignments {
+            self.states.insert(&assignment.name, &assignment.state);
+            // NOTE Like git, macros are only expanded when they are set.
+            if assignment.state != State::Set || depth >= MAX_MACRO_DEPTH {
+                continue;
+            }
+            if let Some(expanded) = self.macros.get(&assignment.name) {
+                self.apply(expanded, depth + 1);
+            }
+        }
+    }
+
+    fn get(&self, name: &str) -> Option<&State> {
+        self.states
+            .get(name)
+            .copied()
+            .filter(|state| **state != State::Unspecified)
+    }
+
+    fn flag(&self, name: &str) -> Option<bool> {
+        self.get(name).and_then(State::as_bool)
+    }
+
+    fn overrides(&self) -> Overrides {
+        let language = match self.get(LANGUAGE_ATTR) {
+            Some(State::Value(value)) => value.parse().ok(),
+            _ => None,
+        };
+        Overrides {
+            language,
+            is_documentation: self.flag(DOCUMENTATION_ATTR),
+            is_generated: self.flag(GENERATED_ATTR),
+            is_vendored: self.flag(VENDORED_ATTR),
+            is_detectable: self.flag(DETECTABLE_ATTR),
+            is_binary: self.flag(BINARY_ATTR),
+        }
+    }
+}
+
+/// Evaluates `.gitattributes` files in a directory tree.
+///
+/// If the directory is inside a git worktree, the `.gitattributes` files
+/// between the top of the worktree and the directory, and
+/// `$GIT_DIR/info/attributes`, are also evaluated.
+pub(super) struct Attributes {
+    /// The top of the worktree, or `root` if it's not in a worktree.
+    top: PathBuf,
+    /// The path of `root` relative to `top`.
+    prefix: PathBuf,
+    macros: HashMap<String, Vec<Assignment>>,
+    info: Option<AttributesFile>,
+    /// Parsed `.gitattributes` files, keyed by their directory relative to
+    /// `top`.
+    cache: RwLock<HashMap<PathBuf, Option<Arc<AttributesFile>>>>,
+}
+
+impl Attributes {
+    pub(super) const FILENAME: &'static str = ".gitattributes";
+
+    pub(super) fn new(root: &Path) -> io::Result<Self> {
+        let absolute = fs::canonicalize(root)?;
+        let (top, info) = match find_git_dir(&absolute)? {
+            Some((top, git_dir)) => {
+                let info = read_attributes_file(&git_dir.join("info").join("attributes"))?;
+                (top, info)
+            }
+            None => (absolute.clone(), None),
+        };
+        let prefix = absolute
+            .strip_prefix(&top)
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+
+        let top_file = read_attributes_file(&top.join(Self::FILENAME))?;
+        // NOTE Like git, macros can only be defined at the top level.
+        let macros = top_file
+            .iter()
+            .chain(info.iter())
+            .flat_map(|file| file.macros.iter().cloned())
+            .collect();
+        let cache = HashMap::from([(PathBuf::new(), top_file.map(Arc::new))]);
+
+        Ok(Self {
+            top,
+            prefix,
+            macros,
+            info,
+            cache: RwLock::new(cache),
+        })
+    }
+
+    /// Gets the overrides for a path relative to the root.
+    pub(super) fn overrides(&self, path: &Path) -> Overrides {
+        let path = self.prefix.join(path);
+
+        let ancestors: Vec<_> = path
+            .parent()
+            .into_iter()
+            .flat_map(Path::ancestors)
+            .collect();
+        let files: Vec<_> = ancestors
+            .iter()
+            .rev()
+            .filter_map(|dir| Some((dir, self.file(dir)?)))
+            .collect();
+
+        let mut resolved = Resolved::new(&self.macros);
+        for (dir, file) in &files {
+            let relative = path.strip_prefix(dir).unwrap_or(&path);
+            resolved.apply_file(file, relative);
+        }
+        if let Some(info) = &self.info {
+            resolved.apply_file(info, &path);
+        }
+        resolved.overrides()
+    }
+
+    /// Gets the parsed `.gitattributes` file in a directory relative to the top
+    /// of the worktree.
+    fn file(&self, dir: &Path) -> Option<Arc<AttributesFile>> {
+        if let Some(file) = self
+            .cache
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(dir)
+        {
+            return file.clone();
+        }
+        // NOTE If the file can't be read, it is treated as missing.
+        let file = read_attributes_file(&self.top.join(dir).join(Self::FILENAME))
+            .ok()
+            .flatten()
+            .map(Arc::new);
+        self.cache
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(dir.to_owned(), file.clone());
+        file
+    }
+}
+
+fn read_attributes_file(path: &Path) -> io::Result<Option<AttributesFile>> {
+    match fs::read(path) {
+        Ok(contents) => Ok(Some(AttributesFile::parse(&String::from_utf8_lossy(
+            &contents,
+        )))),
+        Err(err)
+            if matches!(
+                err.kind(),
+                io::ErrorKind::NotFound | io::ErrorKind::NotADirectory
+            ) =>
+        {
+            Ok(None)
+        }
+        Err(err) => Err(err),
+    }
+}
+
+/// Finds the top of the worktree containing `path` and its git directory.
+fn find_git_dir(path: &Path) -> io::Result<Option<(PathBuf, PathBuf)>> {
+    for dir in path.ancestors() {
+        let dot_git = dir.join(".git");
+        if dot_git.is_dir() {
+            return Ok(Some((dir.to_owned(), dot_git)));
+        }
+        if dot_git.is_file() {
+            // NOTE Linked worktrees and submodules use a `gitdir: <path>` file.
+            let contents = fs::read_to_string(&dot_git)?;
+            let Some(git_dir) = contents.trim().strip_prefix("gitdir:") else {
+                continue;
+            };
+            return Ok(Some((dir.to_owned(), dir.join(git_dir.trim()))));
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Language;
+    use rstest::rstest;
+
+    fn resolve(files: &[(&str, &str)], macros: &str, path: &str) -> Overrides {
+        let files: Vec<_> = files
+            .iter()
+            .map(|(dir, contents)| (Path::new(dir), AttributesFile::parse(contents)))
+            .collect();
+        let macros = AttributesFile::parse(macros).macros.into_iter().collect();
+        let path = Path::new(path);
+        let mut resolved = Resolved::new(&macros);
+        for (dir, file) in &files {
+            let Ok(relative) = path.strip_prefix(dir) else {
+                continue;
+            };
+            resolved.apply_file(file, relative);
+        }
+        resolved.overrides()
+    }
+
+    #[rstest(
+        token,
+        expected,
+        case("gengo-generated", Some(Assignment { name: "gengo-generated".into(), state: State::Set })),
+        case("-gengo-generated", Some(Assignment { name: "gengo-generated".into(), state: State::Unset })),
+        case("!gengo-generated", Some(Assignment { name: "gengo-generated".into(), state: State::Unspecified })),
+        case("gengo-language=Rust", Some(Assignment { name: "gengo-language".into(), state: State::Value("Rust".into()) })),
+        case("-", None)
+    )]
+    fn test_parse_assignment(token: &str, expected: Option<Assignment>) {
+        assert_eq!(Assignment::parse(token), expected);
+    }
+
+    #[rstest(
+        path,
+        expected,
+        case("dist/main.js", Some(true)),
+        case("src/dist/main.js", None),
+        case("src/main.min.js", Some(true)),
+        case("src/main.js", None)
+    )]
+    fn test_patterns(path: &str, expected: Option<bool>) {
+        let attributes = "/dist/** gengo-generated\n*.min.js gengo-generated\n";
+        let overrides = resolve(&[("", attributes)], "", path);
+        assert_eq!(overrides.is_generated, expected);
+    }
+
+    #[test]
+    fn test_nested_files_take_precedence() {
+        let files = [
+            ("", "*.js gengo-vendored gengo-language=Typescript\n"),
+            ("lib", "*.js -gengo-vendored\n"),
+            ("lib/inner", "a.js !gengo-language\n"),
+        ];
+        let overrides = resolve(&files, "", "lib/main.js");
+        assert_eq!(overrides.is_vendored, Some(false));
+        assert_eq!(overrides.language, Some(Language::Typescript));
+
+        let overrides = resolve(&files, "", "lib/inner/a.js");
+        assert_eq!(overrides.is_vendored, Some(false));
+        assert_eq!(overrides.language, None);
+
+        let overrides = resolve(&files, "", "main.js");
+        assert_eq!(overrides.is_vendored, Some(true));
+    }
+
+    #[test]
+    fn test_later_lines_take_precedence() {
+        let attributes = "*.js gengo-detectable\nvendor/*.js -gengo-detectable\n";
+        let overrides = resolve(&[("", attributes)], "", "vendor/main.js");
+        assert_eq!(overrides.is_detectable, Some(false));
+    }
+
+    #[test]
+    fn test_macros() {
+        let macros = "[attr]third-party gengo-vendored -gengo-detectable\n";
+        let overrides = resolve(
+            &[("", "extern/** third-party gengo-detectable\n")],
+            macros,
+            "extern/lib.c",
+        );
+        assert_eq!(overrides.is_vendored, Some(true));
+        assert_eq!(overrides.is_detectable, Some(true));
+
+        let overrides = resolve(&[("", "extern/** -third-party\n")], macros, "extern/lib.c");
+        assert_eq!(overrides, Overrides::default());
+    }
+}
@@ -1,42 +1,268 @@
-use super::FileSource;
-use crate::Result;
-use ignore::{Walk, WalkBuilder};
-use std::fs::File;
-use std::io::Read;
+use super::attributes::Attributes;
+use super::{FileSource, Overrides, SymlinkPolicy};
+use crate::analysis::{Diagnostic, DiagnosticKind, Stage};
+use crate::{Result, config};
+use glob::Pattern;
+use ignore::{DirEntry, Walk, WalkBuilder, WalkState};
+use rayon::prelude::{ParallelBridge, ParallelIterator};
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom};
 use std::path::{Path, PathBuf};
 
 /// A file source that reads files from a directory.
 ///
 /// Will try to ignore files be default. See [`WalkBuilder`](ignore::WalkBuilder)
 /// for more information.
+///
+/// Filepaths are relative to the root directory, so that they can be matched by
+/// the same globs regardless of the path that was used to create the file
+/// source. See [`Analysis::prefix_paths`](crate::Analysis::prefix_paths) to
+/// display them with a prefix.
+///
+/// Overrides are read from `.gitattributes` files, using the same attributes as
+/// [`Git`](crate::Git). If the directory is inside a git worktree,
+/// `.gitattributes` files in parent directories and `$GIT_DIR/info/attributes`
+/// are also used.
 pub struct Directory {
+    root: PathBuf,
+    /// The root with symbolic links resolved, which resolved links must be
+    /// in.
+    canonical_root: PathBuf,
     buf_size: usize,
     walk_builder: WalkBuilder,
+    attributes: Attributes,
+    symlinks: SymlinkPolicy,
+    parallel: bool,
 }
 
 pub struct Iter {
     walk: Walk,
+    symlinks: SymlinkPolicy,
 }
 
-// TODO Simpler API than having to copy the same read_limit/buf_size value to multiple places?
 impl Directory {
-    /// Creates a new directory file source.
+    /// Creates a new directory file source with the default walker options.
+    /// Use a [`DirectoryBuilder`] to configure how the directory is walked.
     ///
-    /// Set `buf_size` to a reasonable value for your system. You most likely
-    /// would want to set this to the same value as `read_limit` when building
-    /// a [`Gengo`](crate::Gengo) instance with a [`Builder`](crate::Builder).
+    /// `buf_size` limits how much of a file [`FileSource::contents`] reads.
+    /// Analyses aren't limited by it, since they read as much as the read
+    /// limit of the [`Builder`](crate::Builder) allows, including the
+    /// `read-limit` from `.gengo.toml`.
     pub fn new(path: impl AsRef<Path>, buf_size: usize) -> Result<Self> {
-        let path = path.as_ref();
+        DirectoryBuilder::new(path, buf_size).build()
+    }
+
+    /// Converts an error from walking the directory into a diagnostic.
+    fn walk_diagnostic(&self, err: ignore::Error) -> Diagnostic {
+        fn path(err: &ignore::Error) -> Option<&Path> {
+            match err {
+                ignore::Error::WithPath { path, .. } => Some(path),
+                ignore::Error::WithDepth { err, .. }
+                | ignore::Error::WithLineNumber { err, .. } => path(err),
+                ignore::Error::Loop { child, .. } => Some(child),
+                _ => None,
+            }
+        }
+        let path = path(&err).map(|path| path.strip_prefix(&self.root).unwrap_or(path).to_owned());
+        let kind = err
+            .io_error()
+            .map_or(DiagnosticKind::Other, DiagnosticKind::of_io);
+        Diagnostic::new(path, Stage::Walk, kind, err.to_string())
+    }
+
+    /// Checks if an entry is a symbolic link that should be analyzed as the
+    /// path of its target.
+    fn is_link_text(&self, entry: &Path) -> bool {
+        self.symlinks == SymlinkPolicy::Text && entry.is_symlink()
+    }
+
+    /// Checks that an entry that is a resolved symbolic link points to a file
+    /// in the directory, like links are only resolved within the tree by the
+    /// [`Git`](crate::Git) file source.
+    fn check_link(&self, entry: &Path) -> io::Result<()> {
+        if self.symlinks != SymlinkPolicy::Resolve || !entry.is_symlink() {
+            return Ok(());
+        }
+        if fs::canonicalize(entry)?.starts_with(&self.canonical_root) {
+            return Ok(());
+        }
+        Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            "symbolic link target is not in the directory",
+        ))
+    }
+}
+
+/// Builds a [`Directory`] file source with options for walking the directory.
+///
+/// By default, hidden files are skipped, symbolic links to directories are not
+/// followed, symbolic links to files are analyzed as their targets, and files
+/// are ignored by `.gitignore`, `.ignore`, and `.gengoignore` files.
+pub struct DirectoryBuilder {
+    path: PathBuf,
+    buf_size: usize,
+    hidden: bool,
+    follow_links: bool,
+    symlinks: SymlinkPolicy,
+    max_depth: Option<usize>,
+    git_ignore: bool,
+    ignore: bool,
+    gengo_ignore: bool,
+    include: Vec<String>,
+    exclude: Vec<String>,
+    parallel: bool,
+}
+
+impl DirectoryBuilder {
+    /// The name of ignore files that only apply to gengo. They use the same
+    /// syntax as `.gitignore` and take precedence over other ignore files.
+    pub const GENGO_IGNORE_FILENAME: &'static str = ".gengoignore";
+
+    /// Creates a new builder. See [`Directory::new`] for `buf_size`.
+    pub fn new(path: impl AsRef<Path>, buf_size: usize) -> Self {
+        Self {
+            path: path.as_ref().to_owned(),
+            buf_size,
+            hidden: false,
+            follow_links: false,
+            symlinks: SymlinkPolicy::Resolve,
+            max_depth: None,
+            git_ignore: true,
+            ignore: true,
+            gengo_ignore: true,
+            include: Vec::new(),
+            exclude: Vec::new(),
+            parallel: true,
+        }
+    }
+
+    /// Sets if hidden files and directories, like `.github/`, should be
+    /// included. Defaults to `false`.
+    pub fn hidden(mut self, hidden: bool) -> Self {
+        self.hidden = hidden;
+        self
+    }
+
+    /// Sets if symbolic links should be followed. Defaults to `false`.
+    pub fn follow_links(mut self, follow_links: bool) -> Self {
+        self.follow_links = follow_links;
+        self
+    }
+
+    /// Sets how symbolic links to files are analyzed. Defaults to
+    /// [`SymlinkPolicy::Resolve`], which reads the file that they point to.
+    ///
+    /// Links are resolved by the file system, so their targets may be outside
+    /// of the directory.
+    pub fn symlinks(mut self, symlinks: SymlinkPolicy) -> Self {
+        self.symlinks = symlinks;
+        self
+    }
+
+    /// Sets the maximum depth to walk, where files in the root directory have
+    /// a depth of 1. Defaults to no limit.
+    pub fn max_depth(mut self, max_depth: Option<usize>) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Sets if `.gitignore` files, `.git/info/exclude`, and the global git
+    /// ignore file should be respected. Defaults to `true`.
+    pub fn git_ignore(mut self, git_ignore: bool) -> Self {
+        self.git_ignore = git_ignore;
+        self
+    }
+
+    /// Sets if `.ignore` files should be respected. Defaults to `true`.
+    pub fn ignore(mut self, ignore: bool) -> Self {
+        self.ignore = ignore;
+        self
+    }
+
+    /// Sets if `.gengoignore` files should be respected. Defaults to `true`.
+    pub fn gengo_ignore(mut self, gengo_ignore: bool) -> Self {
+        self.gengo_ignore = gengo_ignore;
+        self
+    }
+
+    /// Adds a glob that files must match to be included. If no include globs
+    /// are added, all files are included. Globs are matched against the path
+    /// relative to the root.
+    pub fn include(mut self, glob: impl Into<String>) -> Self {
+        self.include.push(glob.into());
+        self
+    }
+
+    /// Adds a glob for files to exclude. Exclude globs take precedence over
+    /// include globs. Globs are matched against the path relative to the root.
+    pub fn exclude(mut self, glob: impl Into<String>) -> Self {
+        self.exclude.push(glob.into());
+        self
+    }
+
+    /// Sets if the directory should be walked by multiple threads. When this
+    /// is `false`, a single thread walks the directory while the files are
+    /// analyzed in parallel. Defaults to `true`.
+    pub fn parallel(mut self, parallel: bool) -> Self {
+        self.parallel = parallel;
+        self
+    }
+
+    pub fn build(self) -> Result<Directory> {
+        let path = self.path;
         if !path.is_dir() {
             return Err("path is not a directory".into());
         }
-        let walk_builder = WalkBuilder::new(path);
-        let directory = Self {
-            buf_size,
+        let include = Self::patterns(&self.include)?;
+        let exclude = Self::patterns(&self.exclude)?;
+
+        let mut walk_builder = WalkBuilder::new(&path);
+        walk_builder
+            .hidden(!self.hidden)
+            .follow_links(self.follow_links)
+            .max_depth(self.max_depth)
+            .git_ignore(self.git_ignore)
+            .git_global(self.git_ignore)
+            .git_exclude(self.git_ignore)
+            .ignore(self.ignore);
+        if self.gengo_ignore {
+            walk_builder.add_custom_ignore_filename(Self::GENGO_IGNORE_FILENAME);
+        }
+        if !(include.is_empty() && exclude.is_empty()) {
+            let root = path.clone();
+            walk_builder.filter_entry(move |entry| {
+                // NOTE Directories are never filtered, so that globs like
+                //      `src/**/*.rs` can match files in them.
+                if entry.file_type().is_none_or(|file_type| file_type.is_dir()) {
+                    return true;
+                }
+                let filepath = entry.path().strip_prefix(&root).unwrap_or(entry.path());
+                (include.is_empty() || config::matches_any(&include, filepath))
+                    && !config::matches_any(&exclude, filepath)
+            });
+        }
+
+        let attributes = Attributes::new(&path)?;
+        let canonical_root = fs::canonicalize(&path)?;
+        let directory = Directory {
+            root: path,
+            canonical_root,
+            buf_size: self.buf_size,
             walk_builder,
+            attributes,
+            symlinks: self.symlinks,
+            parallel: self.parallel,
         };
         Ok(directory)
     }
+
+    fn patterns(globs: &[String]) -> Result<Vec<Pattern>> {
+        let patterns = globs
+            .iter()
+            .map(|glob| Pattern::new(glob))
+            .collect::<Result<_, _>>()?;
+        Ok(patterns)
+    }
 }
 
 impl<'files> FileSource<'files> for Directory {
@@ -49,7 +275,10 @@ impl<'files> FileSource<'files> for Directory {
     fn entries(&'files self) -> crate::Result<Self::Iter> {
         // NOTE `new` should assert that `path` is always a directory
         let walk = self.walk_builder.build();
-        Ok(Iter { walk })
+        Ok(Iter {
+            walk,
+            symlinks: self.symlinks,
+        })
     }
 
     fn filepath(
@@ -57,35 +286,142 @@ impl<'files> FileSource<'files> for Directory {
         entry: &Self::Entry,
         _state: &mut Self::State,
     ) -> crate::Result<Self::Filepath> {
-        Ok(entry.to_owned())
+        let filepath = entry.strip_prefix(&self.root).unwrap_or(entry);
+        Ok(filepath.to_owned())
     }
 
     fn contents(
         &'files self,
         entry: &Self::Entry,
-        _state: &mut Self::State,
+        state: &mut Self::State,
+    ) -> crate::Result<Self::Contents> {
+        self.contents_prefix(entry, state, self.buf_size)
+    }
+
+    fn contents_prefix(
+        &'files self,
+        entry: &Self::Entry,
+        state: &mut Self::State,
+        limit: usize,
     ) -> crate::Result<Self::Contents> {
-        let mut reader = File::open(entry)?.take(self.buf_size.try_into()?);
-        let mut buf = vec![0; self.buf_size];
-        let read_amount = reader.read(&mut buf)?;
-        buf.resize(read_amount, 0);
+        self.contents_range(entry, state, 0, limit)
+    }
+
+    fn contents_range(
+        &'files self,
+        entry: &Self::Entry,
+        _state: &mut Self::State,
+        offset: usize,
+        limit: usize,
+    ) -> crate::Result<Vec<u8>> {
+        if self.is_link_text(entry) {
+            let target = fs::read_link(entry)?.into_os_string().into_encoded_bytes();
+            let end = offset.saturating_add(limit).min(target.len());
+            return Ok(target[offset.min(end)..end].to_vec());
+        }
+        self.check_link(entry)?;
+        let mut file = File::open(entry)?;
+        let len = usize::try_from(file.metadata()?.len()).unwrap_or(usize::MAX);
+        let mut buf = Vec::with_capacity(limit.min(len.saturating_sub(offset)));
+        file.seek(SeekFrom::Start(offset.try_into()?))?;
+        file.take(limit.try_into().unwrap_or(u64::MAX))
+            .read_to_end(&mut buf)?;
         Ok(buf)
     }
 
+    fn size(
+        &'files self,
+        entry: &Self::Entry,
+        _state: &mut Self::State,
+    ) -> crate::Result<Option<u64>> {
+        if self.is_link_text(entry) {
+            let target = fs::read_link(entry)?;
+            return Ok(Some(target.as_os_str().len().try_into()?));
+        }
+        self.check_link(entry)?;
+        let metadata = fs::metadata(entry)?;
+        Ok(Some(metadata.len()))
+    }
+
+    fn is_symlink(&'files self, entry: &Self::Entry, _state: &mut Self::State) -> bool {
+        entry.is_symlink()
+    }
+
     fn state(&'files self) -> crate::Result<Self::State> {
         Ok(())
     }
+
+    /// Walks the directory with multiple threads, which also read and analyze
+    /// the files, unless parallel walking was disabled.
+    fn visit_entries<F>(&'files self, state: Self::State, visit: F) -> crate::Result<()>
+    where
+        F: Fn(&mut Self::State, Result<Self::Entry, Diagnostic>) + Sync,
+    {
+        let visit = |state: &mut Self::State, entry: Result<DirEntry, ignore::Error>| match entry {
+            Ok(dir_entry) if !is_file(&dir_entry, self.symlinks) => (),
+            Ok(dir_entry) => visit(state, Ok(dir_entry.into_path())),
+            Err(err) => visit(state, Err(self.walk_diagnostic(err))),
+        };
+        if !self.parallel {
+            self.walk_builder
+                .build()
+                .par_bridge()
+                .for_each_with(state, visit);
+            return Ok(());
+        }
+        let visit = &visit;
+        self.walk_builder.build_parallel().run(|| {
+            Box::new(move |entry| {
+                visit(&mut (), entry);
+                WalkState::Continue
+            })
+        });
+        Ok(())
+    }
+
+    fn read_root_file(&'files self, path: &Path) -> crate::Result<Option<Vec<u8>>> {
+        match fs::read(self.root.join(path)) {
+            Ok(contents) => Ok(Some(contents)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn overrides(&self, path: impl AsRef<Path>, _state: &mut Self::State) -> Overrides {
+        self.attributes.overrides(path.as_ref())
+    }
 }
 
 impl Iterator for Iter {
     /// Deeply iterates over the directory.
     type Item = PathBuf;
 
+    /// Errors are skipped. Use [`FileSource::visit_entries`] to report them.
     fn next(&mut self) -> Option<Self::Item> {
-        // TODO Handle error?
         self.walk
-            .next()
-            .and_then(|entry| entry.ok())
-            .map(|dir_entry| dir_entry.path().to_owned())
+            .by_ref()
+            .filter_map(Result::ok)
+            .find(|dir_entry| is_file(dir_entry, self.symlinks))
+            .map(DirEntry::into_path)
+    }
+}
+
+/// Checks if a walked entry should be analyzed as a file. Links to
+/// directories that aren't followed are skipped, unless they're analyzed as
+/// text. Broken links are kept, so that they're reported.
+fn is_file(dir_entry: &DirEntry, symlinks: SymlinkPolicy) -> bool {
+    let is_dir = dir_entry
+        .file_type()
+        .is_some_and(|file_type| file_type.is_dir());
+    if is_dir {
+        return false;
+    }
+    if !dir_entry.path_is_symlink() {
+        return true;
+    }
+    match symlinks {
+        SymlinkPolicy::Skip => false,
+        SymlinkPolicy::Text => true,
+        SymlinkPolicy::Resolve => !dir_entry.path().metadata().is_ok_and(|m| m.is_dir()),
     }
 }
@@ -1,49 +1,360 @@
-use super::{FileSource, Overrides};
+use super::lfs::Pointer;
+use super::objects::Objects;
+use super::{
+    BINARY_ATTR, DETECTABLE_ATTR, DOCUMENTATION_ATTR, FileSource, GENERATED_ATTR, LANGUAGE_ATTR,
+    Lfs, Overrides, SymlinkPolicy, VENDORED_ATTR,
+};
+use crate::analysis::{Diagnostic, Stage};
 use crate::{Error, ErrorKind};
 use gix::{
     Repository, ThreadSafeRepository,
     attrs::StateRef,
     attrs::search::Outcome as AttrOutcome,
-    bstr::ByteSlice,
+    bstr::{BStr, BString, ByteSlice},
     discover::Error as DiscoverError,
     index::{self, entry::Mode as EntryMode},
-    worktree::{Stack as WTStack, stack::state::attributes::Source as AttrSource},
+    object::find::existing::Error as FindError,
+    worktree::{
+        Stack as WTStack,
+        stack::state::{attributes::Source as AttrSource, ignore::Source as IgnoreSource},
+    },
 };
+use rayon::prelude::{ParallelBridge, ParallelIterator};
 use std::borrow::Cow;
-use std::path::Path;
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::iter;
+use std::path::{Path, PathBuf};
 
 use std::slice;
+use std::sync::OnceLock;
 
-struct Builder {
-    repository: ThreadSafeRepository,
-    rev: String,
+/// How the [`Git`] file source analyzes submodules.
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Submodules {
+    /// Submodules are skipped.
+    #[default]
+    Exclude,
+    /// Submodules are analyzed at their pinned commits, like the rest of the
+    /// repository.
+    Include,
+    /// Submodules are analyzed at their pinned commits, and their files are
+    /// marked as vendored unless an attribute says otherwise.
+    Vendored,
+}
+
+/// The files that a [`Git`] file source analyzes.
+enum Target {
+    /// The tree of a committed revision.
+    Revision(String),
+    /// The files staged in the index.
+    Index,
+    /// The files in the worktree, including untracked files that aren't
+    /// ignored.
+    Worktree,
 }
 
-impl Builder {
-    fn new(path: impl AsRef<Path>, rev: &str) -> crate::Result<Self> {
-        let repository = match gix::discover(path) {
+/// Builds a [`Git`] file source.
+///
+/// By default, submodules and symbolic links are skipped.
+pub struct GitBuilder {
+    path: PathBuf,
+    target: Target,
+    submodules: Submodules,
+    symlinks: SymlinkPolicy,
+    pathspecs: Vec<String>,
+    root: Option<PathBuf>,
+    changed_since: Option<String>,
+    changed_lines: bool,
+}
+
+impl GitBuilder {
+    /// Creates a new builder for the repository containing `path`, analyzed
+    /// at `rev`.
+    pub fn new(path: impl AsRef<Path>, rev: &str) -> Self {
+        Self::with_target(path, Target::Revision(rev.to_string()))
+    }
+
+    /// Creates a new builder for the repository containing `path`, analyzing
+    /// the files that are staged in the index.
+    pub fn index(path: impl AsRef<Path>) -> Self {
+        Self::with_target(path, Target::Index)
+    }
+
+    /// Creates a new builder for the repository containing `path`, analyzing
+    /// the files in its worktree. Tracked files are always analyzed, and
+    /// untracked files are analyzed unless they are ignored. Attributes are
+    /// read from the `.gitattributes` files in the worktree.
+    pub fn worktree(path: impl AsRef<Path>) -> Self {
+        Self::with_target(path, Target::Worktree)
+    }
+
+    fn with_target(path: impl AsRef<Path>, target: Target) -> Self {
+        Self {
+            path: path.as_ref().to_owned(),
+            target,
+            submodules: Submodules::default(),
+            symlinks: SymlinkPolicy::Skip,
+            pathspecs: Vec::new(),
+            root: None,
+            changed_since: None,
+            changed_lines: false,
+        }
+    }
+
+    /// Sets how submodules are analyzed. Submodules are resolved from
+    /// `.gitmodules` at the analyzed revision, and are read from the
+    /// repository's `modules` directory, or from their worktree if they were
+    /// cloned there. When analyzing the index or the worktree, submodules are
+    /// analyzed at the commits staged in the index. Defaults to
+    /// [`Submodules::Exclude`].
+    pub fn submodules(mut self, submodules: Submodules) -> Self {
+        self.submodules = submodules;
+        self
+    }
+
+    /// Sets how symbolic links are analyzed. Defaults to
+    /// [`SymlinkPolicy::Skip`].
+    ///
+    /// Links are resolved within the tree of their module, so links to
+    /// absolute paths or to paths outside of the module can't be resolved.
+    /// Links that go through more than 40 other links are assumed to be a
+    /// cycle. Links to directories are found when the file source is built,
+    /// and skipped. Files in the worktree are resolved by the file system
+    /// instead.
+    pub fn symlinks(mut self, symlinks: SymlinkPolicy) -> Self {
+        self.symlinks = symlinks;
+        self
+    }
+
+    /// Adds a pathspec that files must match to be analyzed, like `src`,
+    /// `*.rs`, or `:(exclude)vendor`. Pathspecs use git's syntax and magic,
+    /// and are always relative to the root of the repository. Files are
+    /// filtered before their contents are read. If no pathspecs are added,
+    /// all files are analyzed.
+    pub fn pathspec(mut self, pathspec: impl Into<String>) -> Self {
+        self.pathspecs.push(pathspec.into());
+        self
+    }
+
+    /// Re-roots the analysis at a directory in the repository, so that only
+    /// the files in it are analyzed, and their paths are relative to it.
+    /// Configuration files like `.gengo.toml` are also read from it, but
+    /// pathspecs are still relative to the root of the repository.
+    pub fn reroot(mut self, dir: impl AsRef<Path>) -> Self {
+        self.root = Some(dir.as_ref().to_owned());
+        self
+    }
+
+    /// Only analyzes the files that were added or modified since the merge
+    /// base of `base` and the analyzed commit, like the files that a pull
+    /// request changes. When analyzing the index, it is compared to the
+    /// merge base of `base` and `HEAD`. Submodules are skipped, and the
+    /// worktree can't be analyzed this way.
+    pub fn changed_since(mut self, base: &str) -> Self {
+        self.changed_since = Some(base.to_string());
+        self
+    }
+
+    /// Sets if the files that [changed](Self::changed_since) should be
+    /// weighted by their changed lines. If `true`, the size of a modified
+    /// file is the size of its lines that aren't in the merge base, so that
+    /// summaries show what the changes are written in. Lines are matched
+    /// regardless of their order, so moved lines aren't counted. Files larger
+    /// than 1 MiB, before or after the change, use their full size instead.
+    /// Defaults to `false`, which uses the full size of each changed file.
+    pub fn changed_lines(mut self, changed_lines: bool) -> Self {
+        self.changed_lines = changed_lines;
+        self
+    }
+
+    pub fn build(self) -> crate::Result<Git> {
+        let repository = match gix::discover(&self.path) {
             Ok(r) => r,
             Err(DiscoverError::Discover(err)) => {
                 return Err(Box::new(Error::with_source(ErrorKind::NoRepository, err)));
             }
             Err(err) => return Err(err.into()),
         };
-
-        let repository = repository.into_sync();
-        let rev = rev.to_string();
-        Ok(Self { repository, rev })
+        let index = match &self.target {
+            Target::Revision(rev) => {
+                let tree_id = repository
+                    .rev_parse_single(rev.as_str())?
+                    .object()?
+                    .peel_to_tree()?
+                    .id;
+                repository.index_from_tree(&tree_id)?
+            }
+            Target::Index | Target::Worktree => (**repository.index_or_empty()?).clone(),
+        };
+        let (worktree, attr_source) = match self.target {
+            Target::Worktree => {
+                let workdir = repository
+                    .workdir()
+                    .ok_or("repository does not have a worktree")?
+                    .to_owned();
+                let files = worktree_files(&repository, &workdir, &index, self.symlinks)?;
+                (
+                    Some(Worktree { workdir, files }),
+                    AttrSource::WorktreeThenIdMapping,
+                )
+            }
+            _ => (None, AttrSource::IdMapping),
+        };
+        let base = match &self.changed_since {
+            Some(base) => Some(self.merge_base_index(&repository, base)?),
+            None => None,
+        };
+        let search = if self.pathspecs.is_empty() {
+            None
+        } else {
+            let defaults = repository.pathspec_defaults()?;
+            let patterns = self
+                .pathspecs
+                .iter()
+                .map(|pathspec| gix::pathspec::parse(pathspec.as_bytes(), defaults))
+                .collect::<Result<Vec<_>, _>>()?;
+            let root = repository.workdir().unwrap_or(repository.git_dir());
+            Some(gix::pathspec::Search::from_specs(patterns, None, root)?)
+        };
+        let root = Module::new(repository.into_sync(), index, PathBuf::new(), attr_source)?;
+        let mut git = Git {
+            modules: vec![root],
+            worktree,
+            root: self.root.unwrap_or_default(),
+            submodules: self.submodules,
+            symlinks: self.symlinks,
+            base_blobs: None,
+            diagnostics: Vec::new(),
+        };
+        if let Some(base) = &base {
+            git.keep_changed(base, self.changed_lines);
+        }
+        let repository = git.modules[0].repository.to_thread_local();
+        let mut filter = PathFilter {
+            search,
+            attr_stack: git.modules[0].state.attr_stack.clone(),
+            repository: &repository,
+            root: git.root.clone(),
+        };
+        if git.submodules != Submodules::Exclude && base.is_none() {
+            git.add_submodules(0, &filter);
+        }
+        if filter.search.is_some() || !filter.root.as_os_str().is_empty() {
+            git.filter(&mut filter);
+        }
+        if git.symlinks == SymlinkPolicy::Resolve {
+            git.skip_directory_links()?;
+        }
+        Ok(git)
     }
 
-    /// Constructs a [`State`] for the repository and rev.
-    fn state(&self) -> crate::Result<(State, index::State)> {
-        let repo = self.repository.to_thread_local();
-        let tree_id = repo
-            .rev_parse_single(self.rev.as_str())?
+    /// Gets the tree of the merge base of `base` and the analyzed commit, as
+    /// an index.
+    fn merge_base_index(&self, repository: &Repository, base: &str) -> crate::Result<index::File> {
+        let head = match &self.target {
+            Target::Revision(rev) => repository.rev_parse_single(rev.as_str())?,
+            Target::Index => repository.head_id()?,
+            Target::Worktree => {
+                return Err("changed files can't be analyzed in the worktree".into());
+            }
+        };
+        let head = head.object()?.peel_to_commit()?.id;
+        let base = repository
+            .rev_parse_single(base)?
+            .object()?
+            .peel_to_commit()?
+            .id;
+        let tree_id = repository
+            .merge_base(base, head)?
             .object()?
             .peel_to_tree()?
             .id;
-        let index = repo.index_from_tree(&tree_id)?;
-        let attr_stack = repo.attributes_only(&index, AttrSource::IdMapping)?;
+        Ok(repository.index_from_tree(&tree_id)?)
+    }
+}
+
+/// Filters files by pathspecs and by the directory that the analysis is
+/// re-rooted at.
+struct PathFilter<'a> {
+    search: Option<gix::pathspec::Search>,
+    /// Used for pathspecs with attribute magic, like `:(attr:gengo-vendored)`.
+    attr_stack: WTStack,
+    repository: &'a Repository,
+    root: PathBuf,
+}
+
+impl PathFilter<'_> {
+    /// Checks if a file should be analyzed.
+    fn is_included(&mut self, path: &Path) -> bool {
+        if !path.starts_with(&self.root) {
+            return false;
+        }
+        let Some(search) = &mut self.search else {
+            return true;
+        };
+        let path = gix::path::into_bstr(path);
+        let attr_stack = &mut self.attr_stack;
+        let objects = &self.repository.objects;
+        search
+            .pattern_matching_relative_path(
+                path.as_ref(),
+                Some(false),
+                &mut |path, case, is_dir, out| {
+                    let mode = if is_dir {
+                        EntryMode::DIR
+                    } else {
+                        EntryMode::FILE
+                    };
+                    attr_stack
+                        .set_case(case)
+                        .at_entry(path, Some(mode), objects)
+                        .is_ok_and(|platform| platform.matching_attributes(out))
+                },
+            )
+            .is_some_and(|m| !m.is_excluded())
+    }
+
+    /// Checks if any files in a directory could be analyzed.
+    fn can_include_dir(&self, path: &Path) -> bool {
+        if !(path.starts_with(&self.root) || self.root.starts_with(path)) {
+            return false;
+        }
+        self.search.as_ref().is_none_or(|search| {
+            let path = gix::path::into_bstr(path);
+            search.can_match_relative_path(path.as_ref(), Some(true))
+        })
+    }
+}
+
+/// A repository analyzed at a tree, which is either the root repository or a
+/// submodule.
+struct Module {
+    repository: ThreadSafeRepository,
+    /// The path of the module relative to the root repository.
+    prefix: PathBuf,
+    state: State,
+    index_state: index::State,
+    /// The index before it was filtered, so that symbolic links and
+    /// configuration files can still be read from files that aren't
+    /// analyzed.
+    unfiltered: Option<index::State>,
+    /// The repository's objects, opened when the start of a large object is
+    /// first read.
+    objects: OnceLock<Objects>,
+}
+
+impl Module {
+    fn new(
+        repository: ThreadSafeRepository,
+        index: index::File,
+        prefix: PathBuf,
+        attr_source: AttrSource,
+    ) -> crate::Result<Self> {
+        let repo = repository.to_thread_local();
+        let attr_stack = repo.attributes_only(&index, attr_source)?;
         let attr_matches = attr_stack.selected_attribute_matches(Git::OVERRIDE_ATTRS);
         let (index_state, _) = index.into_parts();
         let attr_stack = attr_stack.detach();
@@ -51,54 +362,603 @@ impl Builder {
             attr_stack,
             attr_matches,
         };
-        Ok((state, index_state))
-    }
-
-    fn build(self) -> crate::Result<Git> {
-        let (state, index_state) = self.state()?;
-        let git = Git {
-            repository: self.repository,
+        Ok(Self {
+            repository,
+            prefix,
             state,
             index_state,
+            unfiltered: None,
+            objects: OnceLock::new(),
+        })
+    }
+
+    /// Maps the paths of submodules to their names, as listed in the
+    /// `.gitmodules` file of the module's tree.
+    fn submodule_names(&self, repository: &Repository) -> crate::Result<HashMap<PathBuf, String>> {
+        let Some(entry) = self.index_state.entry_by_path(".gitmodules".into()) else {
+            return Ok(HashMap::new());
         };
-        Ok(git)
+        let data = repository.find_object(entry.id)?.detach().data;
+        let file = gix::submodule::File::from_bytes(&data, None, &Default::default())?;
+        let names = file
+            .names()
+            .filter_map(|name| {
+                let path = file.path(name).ok()?;
+                Some((gix::path::from_bstring(path), name.to_string()))
+            })
+            .collect();
+        Ok(names)
+    }
+
+    /// Opens a submodule of this module at its pinned commit.
+    fn submodule(
+        &self,
+        repository: &Repository,
+        names: &HashMap<PathBuf, String>,
+        path: &Path,
+        commit: gix::ObjectId,
+    ) -> crate::Result<Self> {
+        let name = names
+            .get(path)
+            .ok_or("submodule is not listed in .gitmodules")?;
+        let git_dir = repository.common_dir().join("modules").join(name);
+        let worktree = repository.workdir().map(|workdir| workdir.join(path));
+        let submodule = if git_dir.is_dir() {
+            gix::open(git_dir)?
+        } else if let Some(worktree) = worktree.filter(|worktree| worktree.join(".git").exists()) {
+            gix::open(worktree)?
+        } else {
+            return Err(Error::new(ErrorKind::NoRepository).into());
+        };
+        let tree_id = match submodule.find_object(commit) {
+            Ok(commit) => commit.peel_to_tree()?.id,
+            Err(err @ FindError::NotFound { .. }) => {
+                return Err(Error::with_source(ErrorKind::MissingObject, err).into());
+            }
+            Err(err) => return Err(err.into()),
+        };
+        let index = submodule.index_from_tree(&tree_id)?;
+        let prefix = self.prefix.join(path);
+        Self::new(submodule.into_sync(), index, prefix, AttrSource::IdMapping)
+    }
+}
+
+/// Reads at most `limit` bytes of a file, starting `offset` bytes into it.
+fn read_range(path: &Path, offset: usize, limit: usize) -> io::Result<Vec<u8>> {
+    let mut file = File::open(path)?;
+    file.seek(SeekFrom::Start(offset.try_into().unwrap_or(u64::MAX)))?;
+    let mut contents = Vec::new();
+    file.take(limit.try_into().unwrap_or(u64::MAX))
+        .read_to_end(&mut contents)?;
+    Ok(contents)
+}
+
+/// The files in the worktree of the root repository.
+struct Worktree {
+    workdir: PathBuf,
+    /// Paths relative to `workdir`.
+    files: Vec<PathBuf>,
+}
+
+/// Lists the files in the worktree that are tracked, or untracked and not
+/// ignored. Like the blobs in a tree, symbolic links are skipped unless the
+/// policy includes them, and links to directories are skipped if they're
+/// resolved. Nested repositories and submodules are also skipped.
+fn worktree_files(
+    repository: &Repository,
+    workdir: &Path,
+    index: &index::State,
+    symlinks: SymlinkPolicy,
+) -> crate::Result<Vec<PathBuf>> {
+    let is_file = |path: &Path, file_type: fs::FileType| {
+        if !file_type.is_symlink() {
+            return file_type.is_file();
+        }
+        match symlinks {
+            SymlinkPolicy::Skip => false,
+            SymlinkPolicy::Text => true,
+            SymlinkPolicy::Resolve => !workdir.join(path).metadata().is_ok_and(|m| m.is_dir()),
+        }
+    };
+    let mut excludes = repository.excludes(index, None, IgnoreSource::default())?;
+    let mut tracked = HashSet::new();
+    let mut gitlinks = HashSet::new();
+    for entry in index.entries() {
+        let path = gix::path::from_bstr(entry.path(index)).into_owned();
+        match entry.mode {
+            EntryMode::FILE | EntryMode::FILE_EXECUTABLE | EntryMode::SYMLINK => {
+                tracked.insert(path)
+            }
+            EntryMode::COMMIT => gitlinks.insert(path),
+            _ => false,
+        };
+    }
+    // NOTE Tracked files are never ignored, even in an ignored directory.
+    let mut files: Vec<_> = tracked
+        .iter()
+        .filter(|path| {
+            fs::symlink_metadata(workdir.join(path)).is_ok_and(|m| is_file(path, m.file_type()))
+        })
+        .cloned()
+        .collect();
+    let mut dirs = vec![PathBuf::new()];
+    while let Some(dir) = dirs.pop() {
+        for dir_entry in fs::read_dir(workdir.join(&dir))? {
+            let dir_entry = dir_entry?;
+            if dir_entry.file_name() == ".git" {
+                continue;
+            }
+            let path = dir.join(dir_entry.file_name());
+            let file_type = dir_entry.file_type()?;
+            if file_type.is_dir() {
+                let nested = gitlinks.contains(&path) || workdir.join(&path).join(".git").exists();
+                if !nested && !excludes.at_path(&path, Some(EntryMode::DIR))?.is_excluded() {
+                    dirs.push(path);
+                }
+            } else if is_file(&path, file_type)
+                && !tracked.contains(&path)
+                && !excludes
+                    .at_path(&path, Some(EntryMode::FILE))?
+                    .is_excluded()
+            {
+                files.push(path);
+            }
+        }
     }
+    files.sort();
+    Ok(files)
 }
 
+/// A file source that reads files from a git revision, the index, or the
+/// worktree.
+///
+/// Submodules can be analyzed at their pinned commits with a [`GitBuilder`].
+/// Their files are prefixed with the path of the submodule.
+///
+/// Both SHA-1 and SHA-256 repositories are supported. The object format is
+/// read from the repository's `extensions.objectFormat` config.
 pub struct Git {
-    repository: ThreadSafeRepository,
-    state: State,
-    index_state: index::State,
+    /// The root repository, followed by its submodules. Nested submodules
+    /// always come after their parent.
+    modules: Vec<Module>,
+    /// The files to analyze instead of the root repository's blobs, when
+    /// analyzing the worktree.
+    worktree: Option<Worktree>,
+    /// The directory that paths are relative to. Empty unless the analysis
+    /// was re-rooted.
+    root: PathBuf,
+    submodules: Submodules,
+    symlinks: SymlinkPolicy,
+    /// The blobs in the merge base of the root repository's modified files,
+    /// by path, when they are weighted by their changed lines.
+    base_blobs: Option<HashMap<BString, gix::ObjectId>>,
+    /// Submodules that couldn't be opened.
+    diagnostics: Vec<Diagnostic>,
 }
 
 impl Git {
-    const OVERRIDE_ATTRS: [&'static str; 5] = [
-        "gengo-language",
-        "gengo-documentation",
-        "gengo-generated",
-        "gengo-vendored",
-        "gengo-detectable",
+    /// The attributes that are read for each file. The overrides come first.
+    const OVERRIDE_ATTRS: [&'static str; 7] = [
+        LANGUAGE_ATTR,
+        DOCUMENTATION_ATTR,
+        GENERATED_ATTR,
+        VENDORED_ATTR,
+        DETECTABLE_ATTR,
+        BINARY_ATTR,
+        "filter",
     ];
     const LANGUAGE_OVERRIDE: usize = 0;
     const DOCUMENTATION_OVERRIDE: usize = 1;
     const GENERATED_OVERRIDE: usize = 2;
     const VENDORED_OVERRIDE: usize = 3;
     const DETECTABLE_OVERRIDE: usize = 4;
+    const BINARY_OVERRIDE: usize = 5;
+    const FILTER_ATTR: usize = 6;
+    /// The maximum number of symbolic links that are followed to resolve one
+    /// path, like Linux's limit.
+    const MAX_SYMLINK_HOPS: usize = 40;
+
+    /// The largest blobs whose lines are compared when they're weighted by
+    /// their changed lines.
+    const MAX_CHANGED_LINES_LEN: u64 = 1 << 20;
+
+    /// Creates a new git file source that skips submodules. Use a
+    /// [`GitBuilder`] to analyze them.
     pub fn new(path: impl AsRef<Path>, rev: &str) -> crate::Result<Self> {
-        Builder::new(path, rev)?.build()
+        GitBuilder::new(path, rev).build()
+    }
+
+    /// Recursively adds the submodules of a module that can contain files
+    /// included by the filter. Submodules that can't be opened, such as
+    /// submodules that were never initialized, are reported as diagnostics.
+    fn add_submodules(&mut self, parent: usize, filter: &PathFilter) {
+        let module = &self.modules[parent];
+        let repository = module.repository.to_thread_local();
+        let path_storage = module.index_state.path_backing();
+        let gitlinks: Vec<_> = module
+            .index_state
+            .entries()
+            .iter()
+            .filter(|entry| entry.mode == EntryMode::COMMIT)
+            .map(|entry| {
+                let path = gix::path::from_bstr(entry.path_in(path_storage)).into_owned();
+                (path, entry.id)
+            })
+            .filter(|(path, _)| filter.can_include_dir(&module.prefix.join(path)))
+            .collect();
+        if gitlinks.is_empty() {
+            return;
+        }
+        // NOTE An invalid .gitmodules is reported for each submodule below.
+        let names = module.submodule_names(&repository).unwrap_or_default();
+        for (path, commit) in gitlinks {
+            let module = &self.modules[parent];
+            match module.submodule(&repository, &names, &path, commit) {
+                Ok(submodule) => {
+                    self.modules.push(submodule);
+                    self.add_submodules(self.modules.len() - 1, filter);
+                }
+                Err(err) => {
+                    let path = module.prefix.join(&path);
+                    let diagnostic = Diagnostic::from_error(Some(path), Stage::Walk, err.as_ref());
+                    self.diagnostics.push(diagnostic);
+                }
+            }
+        }
+    }
+
+    /// Removes the files that the filter doesn't include, before any of them
+    /// are read.
+    fn filter(&mut self, filter: &mut PathFilter) {
+        for module in &mut self.modules {
+            module
+                .unfiltered
+                .get_or_insert_with(|| module.index_state.clone());
+            let prefix = &module.prefix;
+            module.index_state.remove_entries(|_, path, entry| {
+                let path = prefix.join(gix::path::from_bstr(path));
+                is_file(entry, SymlinkPolicy::Text) && !filter.is_included(&path)
+            });
+        }
+        if let Some(worktree) = &mut self.worktree {
+            worktree.files.retain(|path| filter.is_included(path));
+        }
+    }
+
+    /// Removes the root repository's files that are the same in the merge
+    /// base, so that only added or modified files are analyzed. If `lines` is
+    /// set, the merge base's blobs are kept to weight the modified files by
+    /// their changed lines.
+    fn keep_changed(&mut self, base: &index::State, lines: bool) {
+        let module = &mut self.modules[0];
+        module
+            .unfiltered
+            .get_or_insert_with(|| module.index_state.clone());
+        module.index_state.remove_entries(|_, path, entry| {
+            is_file(entry, SymlinkPolicy::Text)
+                && base
+                    .entry_by_path(path)
+                    .is_some_and(|base| base.id == entry.id)
+        });
+        if lines {
+            let index_state = &module.index_state;
+            let blobs = index_state
+                .entries()
+                .iter()
+                .filter_map(|entry| {
+                    let path = entry.path(index_state);
+                    let base = base.entry_by_path(path)?;
+                    Some((path.to_owned(), base.id))
+                })
+                .collect();
+            self.base_blobs = Some(blobs);
+        }
+    }
+
+    /// Gets the size of the lines of a file in the root repository that
+    /// aren't in the merge base. Added files are counted in full.
+    fn changed_size(
+        &self,
+        entry: &index::Entry,
+        base_blobs: &HashMap<BString, gix::ObjectId>,
+        state: &mut <Self as FileSource<'_>>::State,
+    ) -> crate::Result<u64> {
+        let path = entry.path(&self.modules[0].index_state);
+        let (_, repository) = &state[0];
+        let size = repository.find_header(entry.id)?.size();
+        let Some(&base) = base_blobs.get(path) else {
+            return Ok(size);
+        };
+        let base_size = repository.find_header(base)?.size();
+        if size.max(base_size) > Self::MAX_CHANGED_LINES_LEN {
+            return Ok(size);
+        }
+        let head = self.blob_prefix(0, entry.id, state, usize::MAX)?;
+        let base = self.blob_prefix(0, base, state, usize::MAX)?;
+        Ok(changed_len(&base, &head))
+    }
+
+    /// Makes a path relative to the directory that the analysis is re-rooted
+    /// at.
+    fn reroot<'a>(&self, path: Cow<'a, Path>) -> Cow<'a, Path> {
+        match path {
+            Cow::Borrowed(path) => Cow::Borrowed(path.strip_prefix(&self.root).unwrap_or(path)),
+            Cow::Owned(path) => match path.strip_prefix(&self.root) {
+                Ok(path) => Cow::Owned(path.to_owned()),
+                Err(_) => Cow::Owned(path),
+            },
+        }
+    }
+
+    /// Reads at most `limit` bytes of a blob in a module.
+    fn blob_prefix(
+        &self,
+        module: usize,
+        id: gix::ObjectId,
+        state: &mut <Self as FileSource<'_>>::State,
+        limit: usize,
+    ) -> crate::Result<Vec<u8>> {
+        let (_, repository) = &state[module];
+        let size = repository.find_header(id)?.size();
+        if size > limit.try_into().unwrap_or(u64::MAX) {
+            let module = &self.modules[module];
+            let objects = module
+                .objects
+                .get_or_init(|| Objects::open(module.repository.objects_dir(), id.kind()));
+            if let Some(contents) = objects.prefix(&id, limit)? {
+                return Ok(contents);
+            }
+        }
+        let blob = match repository.find_object(id) {
+            Ok(blob) => blob,
+            Err(err @ FindError::NotFound { .. }) => {
+                return Err(Error::with_source(ErrorKind::MissingObject, err).into());
+            }
+            Err(err) => return Err(err.into()),
+        };
+        let mut contents = blob.detach().data;
+        if contents.len() > limit {
+            contents.truncate(limit);
+            contents.shrink_to_fit();
+        }
+        Ok(contents)
+    }
+
+    /// Gets the blob that is analyzed for an entry of a module. Symbolic links
+    /// are resolved to their targets if the policy resolves them.
+    fn resolve<'repo>(
+        &'repo self,
+        module: usize,
+        entry: &'repo index::Entry,
+        state: &mut <Self as FileSource<'_>>::State,
+    ) -> crate::Result<&'repo index::Entry> {
+        if entry.mode != EntryMode::SYMLINK || self.symlinks != SymlinkPolicy::Resolve {
+            return Ok(entry);
+        }
+        let path = self.link_target(module, entry, state)?;
+        self.unfiltered_index(module)
+            .entry_by_path(path.as_bstr())
+            .filter(|entry| is_file(entry, SymlinkPolicy::Skip))
+            .ok_or_else(|| link_not_found().into())
+    }
+
+    /// Gets the path in a module that a symbolic link resolves to, following
+    /// any links on the way.
+    fn link_target(
+        &self,
+        module: usize,
+        entry: &index::Entry,
+        state: &mut <Self as FileSource<'_>>::State,
+    ) -> crate::Result<BString> {
+        let index_state = self.unfiltered_index(module);
+        // NOTE The link's parent directories are never links themselves.
+        let mut resolved: Vec<BString> = entry
+            .path(index_state)
+            .split_str("/")
+            .map(BString::from)
+            .collect();
+        resolved.pop();
+        // NOTE Components are popped from the end, so they're pushed in
+        //      reverse.
+        let mut pending: Vec<BString> = Vec::new();
+        let mut link = entry;
+        let mut hops = 0;
+        loop {
+            hops += 1;
+            if hops > Self::MAX_SYMLINK_HOPS {
+                return Err(io::Error::other("too many levels of symbolic links").into());
+            }
+            let target = self.blob_prefix(module, link.id, state, usize::MAX)?;
+            if target.starts_with(b"/") {
+                return Err(link_not_found().into());
+            }
+            pending.extend(target.split(|&b| b == b'/').rev().map(BString::from));
+            // NOTE Walks the components until one of them is another link.
+            let next = loop {
+                let Some(component) = pending.pop() else {
+                    break None;
+                };
+                match component.as_slice() {
+                    b"" | b"." => continue,
+                    b".." => {
+                        resolved.pop().ok_or_else(link_not_found)?;
+                        continue;
+                    }
+                    _ => resolved.push(component),
+                }
+                let path = resolved.join(&b'/');
+                let entry = index_state.entry_by_path(path.as_bstr());
+                if let Some(entry) = entry.filter(|entry| entry.mode == EntryMode::SYMLINK) {
+                    resolved.pop();
+                    break Some(entry);
+                }
+            };
+            match next {
+                Some(next) => link = next,
+                None => break,
+            }
+        }
+        Ok(resolved.join(&b'/').into())
+    }
+
+    /// The entries of a module before they were filtered, so that links can
+    /// be resolved to files that aren't analyzed.
+    fn unfiltered_index(&self, module: usize) -> &index::State {
+        let module = &self.modules[module];
+        module.unfiltered.as_ref().unwrap_or(&module.index_state)
+    }
+
+    /// Removes the symbolic links that resolve to directories, since the
+    /// files in them are analyzed on their own. Links that can't be resolved
+    /// are kept, so that they're reported.
+    fn skip_directory_links(&mut self) -> crate::Result<()> {
+        let mut state = self.state()?;
+        for module in 0..self.modules.len() {
+            let index_state = &self.modules[module].index_state;
+            let mut links = HashSet::new();
+            for entry in index_state.entries() {
+                if entry.mode != EntryMode::SYMLINK {
+                    continue;
+                }
+                let Ok(target) = self.link_target(module, entry, &mut state) else {
+                    continue;
+                };
+                if self.is_directory(module, target.as_bstr()) {
+                    links.insert(entry.path(index_state).to_owned());
+                }
+            }
+            if links.is_empty() {
+                continue;
+            }
+            let module = &mut self.modules[module];
+            module
+                .unfiltered
+                .get_or_insert_with(|| module.index_state.clone());
+            module
+                .index_state
+                .remove_entries(|_, path, _| links.contains(path));
+        }
+        Ok(())
+    }
+
+    /// Checks if a path in a module is a directory or a submodule.
+    fn is_directory(&self, module: usize, path: &BStr) -> bool {
+        let index_state = self.unfiltered_index(module);
+        if let Some(entry) = index_state.entry_by_path(path) {
+            return entry.mode == EntryMode::COMMIT;
+        }
+        let mut prefix = BString::from(path);
+        prefix.push(b'/');
+        index_state
+            .prefixed_entries(prefix.as_bstr())
+            .is_some_and(|entries| !entries.is_empty())
+    }
+
+    /// Reads at most `limit` bytes of a file in the worktree, starting
+    /// `offset` bytes into it. Symbolic links are read as the path of their
+    /// target unless they are resolved.
+    fn file_range(&self, path: &Path, offset: usize, limit: usize) -> io::Result<Vec<u8>> {
+        let path = self.worktree_path(path);
+        if self.symlinks == SymlinkPolicy::Text && path.is_symlink() {
+            let target = fs::read_link(path)?.into_os_string().into_encoded_bytes();
+            let end = offset.saturating_add(limit).min(target.len());
+            return Ok(target[offset.min(end)..end].to_vec());
+        }
+        read_range(&path, offset, limit)
+    }
+
+    /// Gets the size of a file in the worktree, like [`Self::file_range`].
+    fn file_size(&self, path: &Path) -> crate::Result<u64> {
+        let path = self.worktree_path(path);
+        if self.symlinks == SymlinkPolicy::Text && path.is_symlink() {
+            let target = fs::read_link(path)?;
+            return Ok(target.as_os_str().len().try_into()?);
+        }
+        Ok(fs::metadata(path)?.len())
+    }
+
+    /// Gets the path of an LFS pointer's real file, if the local LFS object
+    /// store has it.
+    fn lfs_object(
+        &self,
+        entry: &Entry,
+        pointer: &Pointer,
+        state: &mut <Self as FileSource<'_>>::State,
+    ) -> Option<PathBuf> {
+        let module = match entry {
+            Entry::Blob(module, _) => *module,
+            Entry::File(_) => 0,
+        };
+        let (_, repository) = &state[module];
+        let path = pointer.object_path(repository.common_dir());
+        path.is_file().then_some(path)
+    }
+
+    /// Checks if a file in the root repository has the `filter=lfs` attribute.
+    fn has_lfs_filter(&self, path: &Path, state: &mut <Self as FileSource<'_>>::State) -> bool {
+        let (state, repository) = &mut state[0];
+        let Ok(platform) = state.attr_stack.at_path(
+            path,
+            Some(EntryMode::FILE | EntryMode::FILE_EXECUTABLE),
+            &repository.objects,
+        ) else {
+            return false;
+        };
+        platform.matching_attributes(&mut state.attr_matches);
+        state
+            .attr_matches
+            .iter_selected()
+            .nth(Self::FILTER_ATTR)
+            .is_some_and(
+                |info| matches!(info.assignment.state, StateRef::Value(v) if v.as_bstr() == "lfs"),
+            )
+    }
+
+    /// Gets the full path of a file in the worktree.
+    fn worktree_path(&self, path: &Path) -> PathBuf {
+        let workdir = self
+            .worktree
+            .as_ref()
+            .map(|worktree| worktree.workdir.as_path());
+        workdir.unwrap_or(Path::new("")).join(path)
+    }
+
+    /// Finds the deepest module containing `path`, and the path relative to
+    /// that module.
+    fn module_of<'a>(&self, path: &'a Path) -> (usize, &'a Path) {
+        self.modules
+            .iter()
+            .enumerate()
+            .rev()
+            .find_map(|(index, module)| Some((index, path.strip_prefix(&module.prefix).ok()?)))
+            .unwrap_or((0, path))
     }
 }
 
 impl<'repo> FileSource<'repo> for Git {
-    type Entry = &'repo index::Entry;
+    type Entry = Entry<'repo>;
     type Filepath = Cow<'repo, Path>;
     type Contents = Vec<u8>;
-    type State = (State, Repository);
+    /// The state of each module.
+    type State = Vec<(State, Repository)>;
     type Iter = Iter<'repo>;
 
     fn entries(&'repo self) -> crate::Result<Self::Iter> {
-        let entries = self.index_state.entries().iter();
-        Ok(Iter { entries })
+        let mut modules = self.modules.iter().enumerate();
+        let files = match &self.worktree {
+            Some(worktree) => {
+                // NOTE The worktree's files replace the root repository's blobs.
+                modules.next();
+                worktree.files.iter()
+            }
+            None => [].iter(),
+        };
+        Ok(Iter {
+            symlinks: self.symlinks,
+            files,
+            modules,
+            entries: None,
+        })
     }
 
     fn filepath(
@@ -106,33 +966,211 @@ impl<'repo> FileSource<'repo> for Git {
         entry: &Self::Entry,
         _state: &mut Self::State,
     ) -> crate::Result<Self::Filepath> {
-        let path_storage = self.index_state.path_backing();
+        let (module, entry) = match entry {
+            Entry::Blob(module, entry) => (module, entry),
+            Entry::File(path) => return Ok(self.reroot(Cow::Borrowed(path))),
+        };
+        let module = &self.modules[*module];
+        let path_storage = module.index_state.path_backing();
         let path = entry.path_in(path_storage);
-        let path = gix::path::try_from_bstr(path)?;
-        Ok(path)
+        let path = gix::path::try_from_bstr(path)
+            .map_err(|err| Error::with_source(ErrorKind::InvalidPath, err))?;
+        if module.prefix.as_os_str().is_empty() {
+            return Ok(self.reroot(path));
+        }
+        Ok(self.reroot(Cow::Owned(module.prefix.join(path))))
     }
 
     fn contents(
         &'repo self,
         entry: &Self::Entry,
-        (_, repository): &mut Self::State,
+        state: &mut Self::State,
+    ) -> crate::Result<Self::Contents> {
+        self.contents_prefix(entry, state, usize::MAX)
+    }
+
+    /// Reads objects up to `limit` bytes without decompressing the rest of the
+    /// object. Packed deltas only read the instructions that produce the
+    /// first `limit` bytes, and the parts of their base that those copy from.
+    ///
+    /// If the file is an LFS pointer and the local LFS object store has the
+    /// real file, the real file is read instead.
+    fn contents_prefix(
+        &'repo self,
+        entry: &Self::Entry,
+        state: &mut Self::State,
+        limit: usize,
     ) -> crate::Result<Self::Contents> {
-        let blob = repository.find_object(entry.id)?;
-        let blob = blob.detach();
-        let contents = blob.data;
+        self.contents_range(entry, state, 0, limit)
+    }
+
+    /// Objects are decompressed from their start, so the bytes before
+    /// `offset` are decompressed again, but aren't kept. Files in the
+    /// worktree and LFS objects are read from `offset`.
+    fn contents_range(
+        &'repo self,
+        entry: &Self::Entry,
+        state: &mut Self::State,
+        offset: usize,
+        limit: usize,
+    ) -> crate::Result<Vec<u8>> {
+        let end = offset.saturating_add(limit);
+        // NOTE Pointers are read in full so that they can be parsed.
+        let mut contents = match entry {
+            Entry::Blob(module, entry) => {
+                let entry = self.resolve(*module, entry, state)?;
+                self.blob_prefix(*module, entry.id, state, end.max(Pointer::MAX_LEN))?
+            }
+            Entry::File(path) if self.file_size(path)? < Pointer::MAX_LEN as u64 => {
+                self.file_range(path, 0, Pointer::MAX_LEN)?
+            }
+            Entry::File(path) => return Ok(self.file_range(path, offset, limit)?),
+        };
+        let object =
+            Pointer::parse(&contents).and_then(|pointer| self.lfs_object(entry, &pointer, state));
+        if let Some(object) = object {
+            return Ok(read_range(&object, offset, limit)?);
+        }
+        contents.truncate(end);
+        contents.drain(..offset.min(contents.len()));
         Ok(contents)
     }
 
+    fn size(
+        &'repo self,
+        entry: &Self::Entry,
+        state: &mut Self::State,
+    ) -> crate::Result<Option<u64>> {
+        let (module, entry) = match entry {
+            Entry::Blob(module, entry) => (module, entry),
+            Entry::File(path) => return Ok(Some(self.file_size(path)?)),
+        };
+        let entry = self.resolve(*module, entry, state)?;
+        let (_, repository) = &state[*module];
+        let header = repository.find_header(entry.id)?;
+        Ok(Some(header.size()))
+    }
+
+    /// Files in the root repository are weighted by their changed lines when
+    /// analyzing changes with [`GitBuilder::changed_lines`].
+    fn weight(
+        &'repo self,
+        entry: &Self::Entry,
+        state: &mut Self::State,
+    ) -> crate::Result<Option<u64>> {
+        let (Entry::Blob(0, entry), Some(base_blobs)) = (entry, &self.base_blobs) else {
+            return Ok(None);
+        };
+        let entry = self.resolve(0, entry, state)?;
+        Ok(Some(self.changed_size(entry, base_blobs, state)?))
+    }
+
+    /// Files are LFS pointers if their contents are a valid pointer. Files in
+    /// the worktree with the `filter=lfs` attribute are also stored in LFS,
+    /// but have already been replaced by their real contents.
+    fn lfs(
+        &'repo self,
+        entry: &Self::Entry,
+        state: &mut Self::State,
+    ) -> crate::Result<Option<Lfs>> {
+        let contents = match entry {
+            Entry::Blob(module, entry) => {
+                let entry = self.resolve(*module, entry, state)?;
+                let (_, repository) = &state[*module];
+                if repository.find_header(entry.id)?.size() >= Pointer::MAX_LEN as u64 {
+                    return Ok(None);
+                }
+                self.blob_prefix(*module, entry.id, state, Pointer::MAX_LEN)?
+            }
+            Entry::File(path) => self.file_range(path, 0, Pointer::MAX_LEN)?,
+        };
+        if let Some(pointer) = Pointer::parse(&contents) {
+            let available = self.lfs_object(entry, &pointer, state).is_some();
+            let size = pointer.size();
+            return Ok(Some(Lfs { size, available }));
+        }
+        let Entry::File(path) = entry else {
+            return Ok(None);
+        };
+        if !self.has_lfs_filter(path, state) {
+            return Ok(None);
+        }
+        let size = self.file_size(path)?;
+        Ok(Some(Lfs {
+            size,
+            available: true,
+        }))
+    }
+
+    fn is_symlink(&'repo self, entry: &Self::Entry, _state: &mut Self::State) -> bool {
+        match entry {
+            Entry::Blob(_, entry) => entry.mode == EntryMode::SYMLINK,
+            Entry::File(path) => self.worktree_path(path).is_symlink(),
+        }
+    }
+
+    /// Symbolic links aren't identified, since what they resolve to can
+    /// change without them changing. Neither are blobs that are weighted by
+    /// their changed lines, since their size depends on the merge base.
+    fn content_id(&'repo self, entry: &Self::Entry, _state: &mut Self::State) -> Option<Vec<u8>> {
+        match entry {
+            Entry::Blob(_, entry)
+                if entry.mode != EntryMode::SYMLINK && self.base_blobs.is_none() =>
+            {
+                Some(entry.id.as_bytes().to_vec())
+            }
+            _ => None,
+        }
+    }
+
     fn state(&'repo self) -> crate::Result<Self::State> {
-        Ok((self.state.clone(), self.repository.to_thread_local()))
+        let state = self
+            .modules
+            .iter()
+            .map(|module| (module.state.clone(), module.repository.to_thread_local()))
+            .collect();
+        Ok(state)
     }
 
-    fn overrides(
-        &self,
-        path: impl AsRef<Path>,
-        (state, repository): &mut Self::State,
-    ) -> Overrides {
-        let path = path.as_ref();
+    /// Reports submodules that couldn't be opened before visiting the entries.
+    fn visit_entries<F>(&'repo self, state: Self::State, visit: F) -> crate::Result<()>
+    where
+        F: Fn(&mut Self::State, Result<Self::Entry, Diagnostic>) + Sync,
+    {
+        let mut diagnostic_state = state.clone();
+        for diagnostic in &self.diagnostics {
+            visit(&mut diagnostic_state, Err(diagnostic.clone()));
+        }
+        self.entries()?
+            .par_bridge()
+            .for_each_with(state, |state, entry| visit(state, Ok(entry)));
+        Ok(())
+    }
+
+    fn read_root_file(&'repo self, path: &Path) -> crate::Result<Option<Vec<u8>>> {
+        let path = &self.root.join(path);
+        if self.worktree.is_some() {
+            return match fs::read(self.worktree_path(path)) {
+                Ok(contents) => Ok(Some(contents)),
+                Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+                Err(err) => Err(err.into()),
+            };
+        }
+        let root = &self.modules[0];
+        let index_state = root.unfiltered.as_ref().unwrap_or(&root.index_state);
+        let path = gix::path::into_bstr(path);
+        let Some(entry) = index_state.entry_by_path(path.as_ref()) else {
+            return Ok(None);
+        };
+        let repository = root.repository.to_thread_local();
+        let blob = repository.find_object(entry.id)?.detach();
+        Ok(Some(blob.data))
+    }
+
+    fn overrides(&self, path: impl AsRef<Path>, state: &mut Self::State) -> Overrides {
+        let path = self.root.join(path);
+        let (module, path) = self.module_of(&path);
+        let (state, repository) = &mut state[module];
         let Ok(platform) = state.attr_stack.at_path(
             path,
             Some(EntryMode::FILE | EntryMode::FILE_EXECUTABLE),
@@ -144,7 +1182,7 @@ impl<'repo> FileSource<'repo> for Git {
         platform.matching_attributes(&mut state.attr_matches);
 
         let attrs = {
-            let mut attrs = [None, None, None, None, None];
+            let mut attrs = [None, None, None, None, None, None];
             state
                 .attr_matches
                 .iter_selected()
@@ -173,10 +1211,14 @@ impl<'repo> FileSource<'repo> for Git {
             .map(|info| info.assignment.state.is_set());
         let is_vendored = attrs[Self::VENDORED_OVERRIDE]
             .as_ref()
-            .map(|info| info.assignment.state.is_set());
+            .map(|info| info.assignment.state.is_set())
+            .or((module != 0 && self.submodules == Submodules::Vendored).then_some(true));
         let is_detectable = attrs[Self::DETECTABLE_OVERRIDE]
             .as_ref()
             .map(|info| info.assignment.state.is_set());
+        let is_binary = attrs[Self::BINARY_OVERRIDE]
+            .as_ref()
+            .map(|info| info.assignment.state.is_set());
 
         Overrides {
             language,
@@ -184,25 +1226,82 @@ impl<'repo> FileSource<'repo> for Git {
             is_generated,
             is_vendored,
             is_detectable,
+            is_binary,
         }
     }
 }
 
+/// Counts the bytes of the lines in `new` that aren't in `old`. Lines are
+/// matched regardless of their order.
+fn changed_len(old: &[u8], new: &[u8]) -> u64 {
+    let mut old_lines: HashMap<&[u8], usize> = HashMap::new();
+    for line in old.split_inclusive(|&b| b == b'\n') {
+        *old_lines.entry(line).or_default() += 1;
+    }
+    new.split_inclusive(|&b| b == b'\n')
+        .filter(|line| match old_lines.get_mut(line) {
+            Some(count) if *count > 0 => {
+                *count -= 1;
+                false
+            }
+            _ => true,
+        })
+        .map(|line| line.len() as u64)
+        .sum()
+}
+
+/// The error of a symbolic link that doesn't resolve to a file in the tree.
+fn link_not_found() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::NotFound,
+        "symbolic link target is not in the tree",
+    )
+}
+
+/// Checks if an entry is a file that is analyzed with a symbolic link policy.
+fn is_file(entry: &index::Entry, symlinks: SymlinkPolicy) -> bool {
+    match entry.mode {
+        EntryMode::FILE | EntryMode::FILE_EXECUTABLE => true,
+        EntryMode::SYMLINK => symlinks != SymlinkPolicy::Skip,
+        _ => false,
+    }
+}
+
+/// An entry in a [`Git`] file source.
+#[derive(Clone, Copy)]
+pub enum Entry<'repo> {
+    /// A blob in the tree of a module, by the index of the module.
+    Blob(usize, &'repo index::Entry),
+    /// A file in the worktree, relative to the root of the worktree.
+    File(&'repo Path),
+}
+
 pub struct Iter<'repo> {
-    entries: slice::Iter<'repo, index::Entry>,
+    symlinks: SymlinkPolicy,
+    files: slice::Iter<'repo, PathBuf>,
+    modules: iter::Enumerate<slice::Iter<'repo, Module>>,
+    entries: Option<(usize, slice::Iter<'repo, index::Entry>)>,
 }
 
 impl<'repo> Iterator for Iter<'repo> {
-    type Item = &'repo index::Entry;
+    type Item = Entry<'repo>;
 
+    /// Iterates over the files in the worktree, if any, and then the files of
+    /// each module in turn.
     fn next(&mut self) -> Option<Self::Item> {
-        let entry = loop {
-            let entry = self.entries.next()?;
-            if matches!(entry.mode, EntryMode::FILE | EntryMode::FILE_EXECUTABLE) {
-                break entry;
+        if let Some(path) = self.files.next() {
+            return Some(Entry::File(path));
+        }
+        loop {
+            if let Some((module, entries)) = &mut self.entries {
+                let entry = entries.find(|entry| is_file(entry, self.symlinks));
+                if let Some(entry) = entry {
+                    return Some(Entry::Blob(*module, entry));
+                }
             }
-        };
-        Some(entry)
+            let (module, next) = self.modules.next()?;
+            self.entries = Some((module, next.index_state.entries().iter()));
+        }
     }
 }
 
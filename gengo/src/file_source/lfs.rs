@@ -0,0 +1,127 @@
+//! Parses [Git LFS pointer files](https://github.com/git-lfs/git-lfs/blob/main/docs/spec.md).
+use std::path::{Path, PathBuf};
+use std::str;
+
+/// The `version` values of pointer files.
+const VERSIONS: [&str; 2] = [
+    "https://git-lfs.github.com/spec/v1",
+    "https://hawser.github.com/spec/v1",
+];
+
+/// A pointer to a file stored in Git LFS.
+#[derive(Debug, PartialEq)]
+pub(super) struct Pointer {
+    /// The SHA-256 of the real file, as lowercase hex.
+    oid: String,
+    /// The size of the real file.
+    size: u64,
+}
+
+impl Pointer {
+    /// Pointer files are always smaller than this, so larger files don't need
+    /// to be checked.
+    pub const MAX_LEN: usize = 1024;
+
+    /// Parses a pointer file. Returns `None` if the contents aren't a valid
+    /// pointer.
+    pub fn parse(contents: &[u8]) -> Option<Self> {
+        if contents.len() >= Self::MAX_LEN {
+            return None;
+        }
+        let contents = str::from_utf8(contents).ok()?;
+        let mut lines = contents.strip_suffix('\n')?.split('\n');
+        let version = lines.next()?.strip_prefix("version ")?;
+        if !VERSIONS.contains(&version) {
+            return None;
+        }
+        let mut oid = None;
+        let mut size = None;
+        for line in lines {
+            let (key, value) = line.split_once(' ')?;
+            match key {
+                "oid" => oid = Some(value.strip_prefix("sha256:")?),
+                "size" => size = Some(value.parse().ok()?),
+                _ => (),
+            }
+        }
+        let oid = oid.filter(|oid| {
+            oid.len() == 64 && oid.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+        })?;
+        Some(Self {
+            oid: oid.to_string(),
+            size: size?,
+        })
+    }
+
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// The path of the real file in a local LFS object store, which is in
+    /// `$GIT_COMMON_DIR/lfs/objects`.
+    pub fn object_path(&self, common_dir: &Path) -> PathBuf {
+        common_dir
+            .join("lfs")
+            .join("objects")
+            .join(&self.oid[..2])
+            .join(&self.oid[2..4])
+            .join(&self.oid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    const OID: &str = "4d7a214614ab2935c943f9e0ff69d22eadbb8f32b1258daaa5e2ca24d17e2393";
+
+    #[rstest(
+        contents,
+        expected,
+        case(
+            format!("version https://git-lfs.github.com/spec/v1\noid sha256:{OID}\nsize 12345\n"),
+            Some(12345)
+        ),
+        case(
+            format!("version https://hawser.github.com/spec/v1\noid sha256:{OID}\nsize 1\n"),
+            Some(1)
+        ),
+        case(
+            format!("version https://git-lfs.github.com/spec/v1\next-0-foo sha256:{OID}\noid sha256:{OID}\nsize 2\n"),
+            Some(2)
+        ),
+        case(
+            format!("version https://git-lfs.github.com/spec/v1\noid sha256:{OID}\nsize 12345"),
+            None
+        ),
+        case(
+            format!("version https://example.com/spec/v1\noid sha256:{OID}\nsize 12345\n"),
+            None
+        ),
+        case(
+            "version https://git-lfs.github.com/spec/v1\noid sha256:abc\nsize 12345\n".into(),
+            None
+        ),
+        case(
+            format!("version https://git-lfs.github.com/spec/v1\noid sha256:{OID}\n"),
+            None
+        ),
+        case("fn main() {}\n".into(), None)
+    )]
+    fn test_parse(contents: String, expected: Option<u64>) {
+        let pointer = Pointer::parse(contents.as_bytes());
+        assert_eq!(pointer.map(|pointer| pointer.size()), expected);
+    }
+
+    #[test]
+    fn test_object_path() {
+        let contents =
+            format!("version https://git-lfs.github.com/spec/v1\noid sha256:{OID}\nsize 1\n");
+        let pointer = Pointer::parse(contents.as_bytes()).unwrap();
+        assert_eq!(
+            pointer.object_path(Path::new(".git")),
+            Path::new(".git/lfs/objects/4d/7a").join(OID)
+        );
+    }
+}
@@ -1,20 +1,36 @@
 //! Provides sources to get files and their attributes.
 
 use crate::Language;
+use crate::analysis::Diagnostic;
+use rayon::prelude::{ParallelBridge, ParallelIterator};
 
 #[cfg(feature = "directory")]
-pub use directory::Directory;
+pub use directory::{Directory, DirectoryBuilder};
 
 #[cfg(feature = "git")]
-pub use git::Git;
+pub use git::{Git, GitBuilder, Submodules};
 use std::marker::{Send, Sync};
 use std::path::Path;
 
+#[cfg(feature = "directory")]
+mod attributes;
 #[cfg(feature = "directory")]
 mod directory;
 
 #[cfg(feature = "git")]
 mod git;
+#[cfg(feature = "git")]
+mod lfs;
+#[cfg(feature = "git")]
+mod objects;
+
+/// Git attributes that override a file's detected attributes.
+const LANGUAGE_ATTR: &str = "gengo-language";
+const DOCUMENTATION_ATTR: &str = "gengo-documentation";
+const GENERATED_ATTR: &str = "gengo-generated";
+const VENDORED_ATTR: &str = "gengo-vendored";
+const DETECTABLE_ATTR: &str = "gengo-detectable";
+const BINARY_ATTR: &str = "gengo-binary";
 
 /// Provides files and overrides.
 pub trait FileSource<'files>: Sync {
@@ -43,9 +59,111 @@ pub trait FileSource<'files>: Sync {
         state: &mut Self::State,
     ) -> crate::Result<Self::Contents>;
 
+    /// Gets at most `limit` bytes from the start of a file's contents. File
+    /// sources should override this to avoid reading more than needed, such as
+    /// for large assets. The default implementation gets all of the contents
+    /// with [`FileSource::contents`].
+    fn contents_prefix(
+        &'files self,
+        entry: &Self::Entry,
+        state: &mut Self::State,
+        _limit: usize,
+    ) -> crate::Result<Self::Contents> {
+        self.contents(entry, state)
+    }
+
+    /// Gets at most `limit` bytes of a file's contents, starting `offset`
+    /// bytes into the file. This is used to read more of a file than was
+    /// first read, and to read the end of a file. The default implementation
+    /// gets the first `offset + limit` bytes with
+    /// [`FileSource::contents_prefix`] and drops the bytes before `offset`.
+    fn contents_range(
+        &'files self,
+        entry: &Self::Entry,
+        state: &mut Self::State,
+        offset: usize,
+        limit: usize,
+    ) -> crate::Result<Vec<u8>> {
+        let end = offset.saturating_add(limit);
+        let contents = self.contents_prefix(entry, state, end)?;
+        let contents = contents.as_ref();
+        let end = end.min(contents.len());
+        Ok(contents[offset.min(end)..end].to_vec())
+    }
+
+    /// Gets the real size of a file from an entry, in bytes, which can be larger
+    /// than the contents if they were truncated. Returns `None` if the size is
+    /// the size of the contents.
+    fn size(
+        &'files self,
+        _entry: &Self::Entry,
+        _state: &mut Self::State,
+    ) -> crate::Result<Option<u64>> {
+        Ok(None)
+    }
+
+    /// Gets how much a file counts towards its language's total, in bytes,
+    /// if that isn't its real size. This is only used for the entry's size
+    /// after the file was classified, which still uses the real size.
+    fn weight(
+        &'files self,
+        _entry: &Self::Entry,
+        _state: &mut Self::State,
+    ) -> crate::Result<Option<u64>> {
+        Ok(None)
+    }
+
+    /// Checks if a file is stored in Git LFS. If it is, its real size is used
+    /// instead of [`FileSource::size`], and if its real contents aren't
+    /// available, it is classified by its path only.
+    fn lfs(
+        &'files self,
+        _entry: &Self::Entry,
+        _state: &mut Self::State,
+    ) -> crate::Result<Option<Lfs>> {
+        Ok(None)
+    }
+
+    /// Checks if a file is a symbolic link. See [`SymlinkPolicy`] for how
+    /// file sources analyze them.
+    fn is_symlink(&'files self, _entry: &Self::Entry, _state: &mut Self::State) -> bool {
+        false
+    }
+
+    /// Identifies the contents of a file, like a git blob ID, so that a file
+    /// whose path and contents didn't change can reuse an earlier result.
+    /// Returns `None` if the contents can't be identified without reading
+    /// them.
+    fn content_id(&'files self, _entry: &Self::Entry, _state: &mut Self::State) -> Option<Vec<u8>> {
+        None
+    }
+
     /// Gets a state that can be shared between iterations.
     fn state(&'files self) -> crate::Result<Self::State>;
 
+    /// Calls `visit` on each entry in parallel. Each thread gets its own clone
+    /// of `state`. Entries that fail to be listed can be reported to `visit`
+    /// as a [`Diagnostic`] so that they are included in the analysis.
+    ///
+    /// The default implementation bridges [`FileSource::entries`] to rayon, so
+    /// a single thread produces the entries. File sources that can produce
+    /// entries in parallel, like a directory walker, should override this.
+    fn visit_entries<F>(&'files self, state: Self::State, visit: F) -> crate::Result<()>
+    where
+        F: Fn(&mut Self::State, Result<Self::Entry, Diagnostic>) + Sync,
+    {
+        self.entries()?
+            .par_bridge()
+            .for_each_with(state, |state, entry| visit(state, Ok(entry)));
+        Ok(())
+    }
+
+    /// Reads a file relative to the root of the file source, such as a
+    /// configuration file. Returns `None` if the file does not exist.
+    fn read_root_file(&'files self, _path: &Path) -> crate::Result<Option<Vec<u8>>> {
+        Ok(None)
+    }
+
     /// Provides combined overrides for the file.
     fn overrides(&self, path: impl AsRef<Path>, state: &mut Self::State) -> Overrides {
         Overrides {
@@ -54,6 +172,7 @@ pub trait FileSource<'files>: Sync {
             is_generated: self.is_generated_override(&path, state),
             is_vendored: self.is_vendored_override(&path, state),
             is_detectable: self.is_detectable_override(&path, state),
+            is_binary: self.is_binary_override(&path, state),
         }
     }
 
@@ -101,14 +220,72 @@ pub trait FileSource<'files>: Sync {
     ) -> Option<bool> {
         None
     }
+
+    /// Provides an optional override for binary file detection.
+    fn is_binary_override(
+        &self,
+        _path: impl AsRef<Path>,
+        _state: &mut Self::State,
+    ) -> Option<bool> {
+        None
+    }
+}
+
+/// How a file source analyzes symbolic links.
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SymlinkPolicy {
+    /// Symbolic links are skipped.
+    Skip,
+    /// A symbolic link is analyzed as its own file, whose contents are the
+    /// path of its target.
+    Text,
+    /// A symbolic link is analyzed as the file that it points to. Links to
+    /// directories are skipped, and links that can't be resolved, including
+    /// links to files outside of the tree or directory, are reported as
+    /// diagnostics.
+    Resolve,
+}
+
+/// A file that is stored in Git LFS.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Lfs {
+    /// The size of the real file.
+    pub size: u64,
+    /// If the real contents can be read with [`FileSource::contents`]. If not,
+    /// the contents are only an LFS pointer.
+    pub available: bool,
 }
 
+/// Overrides for a file's detected attributes. `None` means that the attribute
+/// should be detected normally.
 #[non_exhaustive]
-#[derive(Default)]
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
 pub struct Overrides {
     pub language: Option<Language>,
     pub is_documentation: Option<bool>,
     pub is_generated: Option<bool>,
     pub is_vendored: Option<bool>,
     pub is_detectable: Option<bool>,
+    pub is_binary: Option<bool>,
+}
+
+impl Overrides {
+    /// Replaces each override with the one from `other`, if `other` has it set.
+    pub fn overlay(&mut self, other: &Self) {
+        let Self {
+            language,
+            is_documentation,
+            is_generated,
+            is_vendored,
+            is_detectable,
+            is_binary,
+        } = other;
+        self.language = language.or(self.language);
+        self.is_documentation = is_documentation.or(self.is_documentation);
+        self.is_generated = is_generated.or(self.is_generated);
+        self.is_vendored = is_vendored.or(self.is_vendored);
+        self.is_detectable = is_detectable.or(self.is_detectable);
+        self.is_binary = is_binary.or(self.is_binary);
+    }
 }
@@ -0,0 +1,362 @@
+//! Reads the start of git objects without decoding them in full, so that
+//! large blobs are never fully loaded when only their start is analyzed.
+use gix::{
+    hash,
+    odb::pack::{self, data::entry::Header},
+    zlib,
+};
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom};
+use std::iter;
+use std::path::{Path, PathBuf};
+
+/// The object directories of a repository and the indices of their packs.
+pub(super) struct Objects {
+    /// The repository's object directory, followed by its alternates.
+    dirs: Vec<PathBuf>,
+    /// The indices of the packs in all object directories. Repositories with
+    /// a multi-pack index still keep an index for each pack.
+    packs: Vec<pack::index::File>,
+}
+
+impl Objects {
+    /// The maximum length of a loose object's `<kind> <size>\0` header.
+    const MAX_LOOSE_HEADER_LEN: usize = 32;
+
+    /// Finds the object directories and opens the indices of their packs.
+    /// Alternates and packs that can't be opened are left out, so that their
+    /// objects are decoded in full.
+    pub fn open(objects_dir: &Path, object_hash: hash::Kind) -> Self {
+        let current_dir = std::env::current_dir().unwrap_or_default();
+        let alternates =
+            gix::odb::alternate::resolve(objects_dir.to_owned(), &current_dir).unwrap_or_default();
+        let dirs: Vec<_> = iter::once(objects_dir.to_owned())
+            .chain(alternates)
+            .collect();
+        let packs = dirs
+            .iter()
+            .filter_map(|dir| fs::read_dir(dir.join("pack")).ok())
+            .flatten()
+            .filter_map(|dir_entry| {
+                let path = dir_entry.ok()?.path();
+                if path.extension() != Some("idx".as_ref()) {
+                    return None;
+                }
+                pack::index::File::at(path, object_hash).ok()
+            })
+            .collect();
+        Self { dirs, packs }
+    }
+
+    /// Decompresses at most `limit` bytes of an object's data. Returns `None`
+    /// if the object isn't loose or in one of the packs.
+    ///
+    /// Deltas are resolved by only reading their instructions until `limit`
+    /// bytes are produced, and then only reading as much of their base as
+    /// those instructions copy from.
+    pub fn prefix(&self, id: &gix::oid, limit: usize) -> io::Result<Option<Vec<u8>>> {
+        if let Some(contents) = self.loose_prefix(id, limit)? {
+            return Ok(Some(contents));
+        }
+        let Some((index, offset)) = self.packs.iter().find_map(|index| {
+            let entry = index.lookup(id)?;
+            Some((index, index.pack_offset_at_index(entry)))
+        }) else {
+            return Ok(None);
+        };
+        let mut pack = BufReader::new(File::open(index.path().with_extension("pack"))?);
+        self.packed_prefix(&mut pack, offset, id.kind(), limit)
+    }
+
+    /// Decompresses at most `limit` bytes of a loose object's data. Returns
+    /// `None` if the object isn't loose.
+    fn loose_prefix(&self, id: &gix::oid, limit: usize) -> io::Result<Option<Vec<u8>>> {
+        let hex = id.to_hex().to_string();
+        let Some(file) = self.dirs.iter().find_map(|dir| {
+            let path = dir.join(&hex[..2]).join(&hex[2..]);
+            match File::open(path) {
+                Err(err) if err.kind() == io::ErrorKind::NotFound => None,
+                file => Some(file),
+            }
+        }) else {
+            return Ok(None);
+        };
+        let mut data = BufReader::new(Inflate {
+            reader: BufReader::new(file?),
+            state: zlib::Decompress::new(),
+        });
+        let mut header = Vec::new();
+        (&mut data)
+            .take(Self::MAX_LOOSE_HEADER_LEN as u64)
+            .read_until(0, &mut header)?;
+        if header.last() != Some(&0) {
+            return Err(invalid_data("invalid loose object header"));
+        }
+        read_prefix(data, limit).map(Some)
+    }
+
+    /// Decompresses at most `limit` bytes of the object at `offset` in a
+    /// pack. Returns `None` if it is a delta whose base can't be found.
+    fn packed_prefix(
+        &self,
+        pack: &mut BufReader<File>,
+        offset: u64,
+        object_hash: hash::Kind,
+        limit: usize,
+    ) -> io::Result<Option<Vec<u8>>> {
+        pack.seek(SeekFrom::Start(offset))?;
+        let entry = pack::data::Entry::from_read(pack, offset, object_hash.len_in_bytes())?;
+        let mut data = BufReader::new(Inflate {
+            reader: &mut *pack,
+            state: zlib::Decompress::new(),
+        });
+        let (delta, base) = match entry.header {
+            Header::OfsDelta { base_distance } => {
+                let delta = Delta::read(&mut data, limit)?;
+                let base_offset = Header::verified_base_pack_offset(offset, base_distance)
+                    .ok_or_else(|| invalid_data("invalid delta base offset"))?;
+                let base = self.packed_prefix(pack, base_offset, object_hash, delta.base_len)?;
+                (delta, base)
+            }
+            Header::RefDelta { base_id } => {
+                let delta = Delta::read(&mut data, limit)?;
+                let base = self.prefix(&base_id, delta.base_len)?;
+                (delta, base)
+            }
+            _ => return read_prefix(data, limit).map(Some),
+        };
+        base.map(|base| delta.apply(&base)).transpose()
+    }
+}
+
+/// The start of an object that is stored as a delta of a base object.
+struct Delta {
+    /// The bytes of the object, where the bytes that are copied from the base
+    /// are still zeros.
+    contents: Vec<u8>,
+    /// The bytes that are copied from the base, as where they are copied to,
+    /// where they are in the base, and how many there are.
+    copies: Vec<(usize, usize, usize)>,
+    /// How much of the start of the base is copied from.
+    base_len: usize,
+}
+
+impl Delta {
+    /// Reads a delta's instructions until they produce `limit` bytes. See
+    /// [git's docs](https://git-scm.com/docs/pack-format#_deltified_representation).
+    fn read(data: &mut impl Read, limit: usize) -> io::Result<Self> {
+        let _base_size = read_size(data)?;
+        let size = read_size(data)?;
+        let limit = limit.min(usize::try_from(size).unwrap_or(usize::MAX));
+        let mut delta = Self {
+            contents: Vec::new(),
+            copies: Vec::new(),
+            base_len: 0,
+        };
+        while delta.contents.len() < limit {
+            let rest = limit - delta.contents.len();
+            let [op] = read_bytes(data)?;
+            if op & 0x80 == 0 {
+                if op == 0 {
+                    return Err(invalid_data("invalid delta instruction"));
+                }
+                let len = usize::from(op).min(rest);
+                let start = delta.contents.len();
+                delta.contents.resize(start + len, 0);
+                data.read_exact(&mut delta.contents[start..])?;
+                continue;
+            }
+            // NOTE Each set bit means that the next byte is a byte of the
+            //      offset or the length, from least to most significant.
+            let mut args = [0u8; 7];
+            for (i, arg) in args.iter_mut().enumerate() {
+                if op & (1 << i) != 0 {
+                    *arg = read_bytes::<1>(data)?[0];
+                }
+            }
+            let base_offset = u32::from_le_bytes([args[0], args[1], args[2], args[3]]) as usize;
+            let len = match u32::from_le_bytes([args[4], args[5], args[6], 0]) {
+                0 => 0x10000,
+                len => len as usize,
+            }
+            .min(rest);
+            delta.copies.push((delta.contents.len(), base_offset, len));
+            delta.base_len = delta.base_len.max(base_offset + len);
+            delta.contents.resize(delta.contents.len() + len, 0);
+        }
+        Ok(delta)
+    }
+
+    /// Copies the ranges of the base into the object.
+    fn apply(mut self, base: &[u8]) -> io::Result<Vec<u8>> {
+        for (start, base_offset, len) in self.copies {
+            let Some(copied) = base.get(base_offset..base_offset + len) else {
+                return Err(invalid_data("delta copies past the end of its base"));
+            };
+            self.contents[start..start + len].copy_from_slice(copied);
+        }
+        Ok(self.contents)
+    }
+}
+
+/// Decompresses a zlib stream as it is read.
+struct Inflate<R> {
+    reader: R,
+    state: zlib::Decompress,
+}
+
+impl<R: BufRead> Read for Inflate<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        zlib::stream::inflate::read(&mut self.reader, &mut self.state, buf)
+    }
+}
+
+/// Reads at most `limit` bytes.
+fn read_prefix(data: impl Read, limit: usize) -> io::Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    data.take(limit.try_into().unwrap_or(u64::MAX))
+        .read_to_end(&mut buf)?;
+    Ok(buf)
+}
+
+/// Reads a size at the start of a delta, which is encoded in 7-bit groups
+/// from least to most significant.
+fn read_size(data: &mut impl Read) -> io::Result<u64> {
+    let mut size = 0;
+    for shift in (0..u64::BITS).step_by(7) {
+        let [byte] = read_bytes(data)?;
+        size |= u64::from(byte & 0x7F) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(size);
+        }
+    }
+    Err(invalid_data("invalid delta size"))
+}
+
+fn read_bytes<const N: usize>(data: &mut impl Read) -> io::Result<[u8; N]> {
+    let mut bytes = [0; N];
+    data.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn invalid_data(message: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use std::process::Command;
+
+    fn git(root: &Path, args: &[&str]) -> String {
+        let output = Command::new("git")
+            .args([
+                "-c",
+                "user.name=gengo",
+                "-c",
+                "user.email=gengo@example.com",
+            ])
+            .args(args)
+            .current_dir(root)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "git {args:?} failed");
+        String::from_utf8(output.stdout).unwrap().trim().to_string()
+    }
+
+    /// Checks if an object is stored as a delta in one of the packs.
+    fn is_delta(objects: &Objects, id: &gix::oid) -> bool {
+        objects.packs.iter().any(|index| {
+            let Some(entry) = index.lookup(id) else {
+                return false;
+            };
+            let offset = index.pack_offset_at_index(entry);
+            let mut pack = File::open(index.path().with_extension("pack")).unwrap();
+            pack.seek(SeekFrom::Start(offset)).unwrap();
+            let entry = pack::data::Entry::from_read(&mut pack, offset, id.kind().len_in_bytes());
+            entry.unwrap().header.is_delta()
+        })
+    }
+
+    #[rstest(
+        repack,
+        case::loose(&[]),
+        case::ofs_deltas(&["repack", "--quiet", "-adf", "--window=10"]),
+        case::ref_deltas(&["-c", "repack.useDeltaBaseOffset=false", "repack", "--quiet", "-adf", "--window=10"])
+    )]
+    fn test_prefix(repack: &[&str]) {
+        let lines: Vec<_> = (0..5000).map(|i| format!("SELECT {i};\n")).collect();
+        let revisions = [
+            lines.concat(),
+            [
+                "-- changed\n".to_string(),
+                lines[..2500].concat(),
+                lines[2600..].concat(),
+            ]
+            .concat(),
+            [
+                lines[4000..].concat(),
+                lines[..4000].concat(),
+                "SELECT 0;\n".into(),
+            ]
+            .concat(),
+        ];
+        let root = tempfile::tempdir().unwrap();
+        let root = root.path();
+        git(root, &["init", "--quiet"]);
+        let mut ids = Vec::new();
+        for contents in &revisions {
+            fs::write(root.join("large.sql"), contents).unwrap();
+            git(root, &["add", "."]);
+            git(root, &["commit", "--quiet", "-m", "commit"]);
+            ids.push(git(root, &["rev-parse", "HEAD:large.sql"]));
+        }
+        if !repack.is_empty() {
+            git(root, repack);
+        }
+
+        let objects = Objects::open(&root.join(".git/objects"), hash::Kind::Sha1);
+        let ids: Vec<_> = ids
+            .iter()
+            .map(|id| gix::ObjectId::from_hex(id.as_bytes()).unwrap())
+            .collect();
+        assert_eq!(
+            ids.iter().any(|id| is_delta(&objects, id)),
+            !repack.is_empty()
+        );
+        for (id, contents) in ids.iter().zip(&revisions) {
+            for limit in [0, 100, 30_000, contents.len(), usize::MAX / 2] {
+                let prefix = objects.prefix(id, limit).unwrap().unwrap();
+                let end = limit.min(contents.len());
+                assert_eq!(prefix, &contents.as_bytes()[..end], "{id} at {limit}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_prefix_in_alternates() {
+        let contents = "SELECT 1;\n".repeat(1000);
+        let source = tempfile::tempdir().unwrap();
+        git(source.path(), &["init", "--quiet"]);
+        fs::write(source.path().join("large.sql"), &contents).unwrap();
+        git(source.path(), &["add", "."]);
+        git(source.path(), &["commit", "--quiet", "-m", "commit"]);
+        git(source.path(), &["gc", "--quiet"]);
+        let root = tempfile::tempdir().unwrap();
+        let shared = [
+            "clone",
+            "--quiet",
+            "--shared",
+            source.path().to_str().unwrap(),
+            ".",
+        ];
+        git(root.path(), &shared);
+
+        let id = git(root.path(), &["rev-parse", "HEAD:large.sql"]);
+        let id = gix::ObjectId::from_hex(id.as_bytes()).unwrap();
+        let objects = Objects::open(&root.path().join(".git/objects"), hash::Kind::Sha1);
+        let prefix = objects.prefix(&id, 100).unwrap().unwrap();
+        assert_eq!(prefix, &contents.as_bytes()[..100]);
+    }
+}
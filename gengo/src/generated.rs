@@ -1,31 +1,88 @@
 use super::GLOB_MATCH_OPTIONS;
+use crate::Classifier;
 use glob::Pattern;
+use regex::RegexSet;
 use std::collections::HashSet;
 use std::path::Path;
+use std::sync::LazyLock;
 
 pub struct Generated {
     filenames: HashSet<&'static str>,
     globs: Vec<Pattern>,
+    minification: MinificationThresholds,
+}
+
+/// Thresholds for detecting minified files and bundles.
+///
+/// Each signal that passes its threshold adds to a file's score, and files
+/// with a score of at least `min_score` are considered generated.
+/// Source map trailers and bundler signatures are decisive on their own, but
+/// are only checked in JavaScript and CSS files, since other files can mention
+/// them.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct MinificationThresholds {
+    /// How many bytes from the start of the file should be scored.
+    pub window: usize,
+    /// Files with a longer average line length are likely minified.
+    pub average_line_length: usize,
+    /// Files with a lower ratio of whitespace to code are likely minified.
+    pub whitespace_ratio: f64,
+    /// Files with a higher ratio of short (1 or 2 character) identifiers are
+    /// likely minified.
+    pub short_identifier_ratio: f64,
+    /// The score at which a file is considered minified.
+    pub min_score: u32,
+}
+
+impl Default for MinificationThresholds {
+    fn default() -> Self {
+        Self {
+            window: 1 << 15,
+            average_line_length: 200,
+            whitespace_ratio: 0.05,
+            short_identifier_ratio: 0.5,
+            min_score: 2,
+        }
+    }
 }
 
 impl Generated {
-    pub fn new() -> Self {
+    /// How many bytes from the end of a file are first read to find a source
+    /// map trailer. More are read if the last lines are longer.
+    pub const TRAILER_LEN: usize = 1 << 10;
+
+    /// Only check the last few lines, as the trailer should be at the end.
+    const TRAILING_LINES: usize = 3;
+
+    pub fn new(minification: MinificationThresholds) -> Self {
         let filenames = Self::filenames();
         let globs = Self::globs();
 
-        Self { filenames, globs }
+        Self {
+            filenames,
+            globs,
+            minification,
+        }
+    }
+
+    /// Adds globs that match files in addition to the built-in globs.
+    pub fn with_globs(mut self, globs: impl IntoIterator<Item = Pattern>) -> Self {
+        self.globs.extend(globs);
+        self
     }
 
-    pub fn is_generated(&self, filepath: impl AsRef<Path>, contents: &[u8]) -> bool {
-        self.is_generated_no_read(&filepath) || self.is_generated_with_read(&filepath, contents)
+    /// How many bytes from the start of a file are scored.
+    pub fn window(&self) -> usize {
+        self.minification.window
     }
 
     fn is_generated_no_read(&self, filepath: impl AsRef<Path>) -> bool {
         self.matches_filenames(&filepath) || self.matches_globs(&filepath)
     }
 
-    fn is_generated_with_read(&self, _filepath: impl AsRef<Path>, contents: &[u8]) -> bool {
-        self.likely_minified(contents)
+    fn is_generated_with_read(&self, filepath: impl AsRef<Path>, contents: &[u8]) -> bool {
+        self.likely_minified(filepath, contents)
     }
 
     fn matches_filenames(&self, filepath: impl AsRef<Path>) -> bool {
@@ -39,12 +96,131 @@ impl Generated {
             .any(|g| g.matches_path_with(filepath.as_ref(), GLOB_MATCH_OPTIONS))
     }
 
-    fn likely_minified(&self, contents: &[u8]) -> bool {
-        // NOTE If the first 10 lines are really long, it's probably minified.
+    fn likely_minified(&self, filepath: impl AsRef<Path>, contents: &[u8]) -> bool {
+        let thresholds = &self.minification;
+        let window = &contents[..contents.len().min(thresholds.window)];
+        if Self::may_be_bundled(filepath)
+            && (Self::has_source_map_trailer(contents) || Self::has_bundler_signature(window))
+        {
+            return true;
+        }
+        // NOTE Files that are shorter than a single long line are too small to
+        //      score meaningfully.
+        if window.len() <= thresholds.average_line_length {
+            return false;
+        }
+
+        let signals = [
+            Self::average_line_length(window) > thresholds.average_line_length,
+            Self::whitespace_ratio(window) < thresholds.whitespace_ratio,
+            Self::short_identifier_ratio(window)
+                .is_some_and(|ratio| ratio > thresholds.short_identifier_ratio),
+        ];
+        let score: u32 = signals.into_iter().map(u32::from).sum();
+        score >= thresholds.min_score
+    }
+
+    /// Checks if a file is JavaScript or CSS, which bundlers and minifiers
+    /// output.
+    pub fn may_be_bundled(filepath: impl AsRef<Path>) -> bool {
+        let extension = filepath.as_ref().extension().and_then(|ext| ext.to_str());
+        extension.is_some_and(|ext| matches!(ext, "js" | "mjs" | "cjs" | "jsx" | "css"))
+    }
+
+    /// Checks for a `sourceMappingURL` comment at the end of the contents, which
+    /// is added by minifiers, bundlers, and transpilers.
+    pub fn has_source_map_trailer(contents: &[u8]) -> bool {
         contents
-            .split(|&b| b == b'\n')
-            .take(10)
-            .any(|line| line.len() > 250)
+            .rsplit(|&b| b == b'\n')
+            .map(|line| line.trim_ascii())
+            .filter(|line| !line.is_empty())
+            .take(Self::TRAILING_LINES)
+            .any(|line| {
+                [
+                    b"//# sourceMappingURL=",
+                    b"//@ sourceMappingURL=",
+                    b"/*# sourceMappingURL=",
+                ]
+                .iter()
+                .any(|prefix| line.starts_with(*prefix))
+            })
+    }
+
+    /// Checks if the end of a file holds all of the lines that are checked
+    /// for a source map trailer. The first line may have been cut off.
+    pub fn has_trailing_lines(tail: &[u8]) -> bool {
+        tail.rsplit(|&b| b == b'\n')
+            .map(<[u8]>::trim_ascii)
+            .filter(|line| !line.is_empty())
+            .nth(Self::TRAILING_LINES)
+            .is_some()
+    }
+
+    /// Checks for preambles and runtime helpers left by bundlers and minifiers.
+    fn has_bundler_signature(window: &[u8]) -> bool {
+        static SIGNATURES: LazyLock<RegexSet> = LazyLock::new(|| {
+            RegexSet::new([
+                // webpack
+                r"(?m)^/\*{6}/ ",
+                r"(?m)^\s*(?:function )?__webpack_require__\b",
+                // rollup (UMD wrapper)
+                r#"typeof exports ?=== ?['"]object['"] ?&& ?typeof module ?!== ?['"]undefined['"] ?\? ?module\.exports ?= ?factory\("#,
+                // esbuild
+                r"(?m)^var __defProp = Object\.defineProperty;",
+                r"(?m)^var __toESM = ",
+                // terser (with webpack's license extraction)
+                r"^/\*! For license information please see ",
+                r"^\s*!function\([a-zA-Z_$],[a-zA-Z_$]\)\{",
+                // parcel
+                r"(?m)^(?:var )?parcelRequire\w* ?=",
+            ])
+            .unwrap()
+        });
+        let window = String::from_utf8_lossy(window);
+        SIGNATURES.is_match(&window)
+    }
+
+    fn average_line_length(window: &[u8]) -> usize {
+        let lines = window.split(|&b| b == b'\n').count();
+        window.len() / lines
+    }
+
+    fn whitespace_ratio(window: &[u8]) -> f64 {
+        let whitespace = window.iter().filter(|b| b.is_ascii_whitespace()).count();
+        whitespace as f64 / window.len() as f64
+    }
+
+    /// The ratio of identifiers that are 1 or 2 characters long. Returns `None` if
+    /// there aren't any identifiers.
+    fn short_identifier_ratio(window: &[u8]) -> Option<f64> {
+        let is_identifier_start = |b: &u8| b.is_ascii_alphabetic() || matches!(b, b'_' | b'$');
+        let is_identifier = |b: &u8| b.is_ascii_alphanumeric() || matches!(b, b'_' | b'$');
+
+        let mut identifiers = 0;
+        let mut short_identifiers = 0;
+        let mut rest = window;
+        while let Some(start) = rest.iter().position(is_identifier_start) {
+            // NOTE Skip identifier-like characters that are part of a number, like `1e10`.
+            if start > 0 && is_identifier(&rest[start - 1]) {
+                let skip = rest[start..]
+                    .iter()
+                    .take_while(|b| is_identifier(b))
+                    .count();
+                rest = &rest[start + skip..];
+                continue;
+            }
+            let length = rest[start..]
+                .iter()
+                .take_while(|b| is_identifier(b))
+                .count();
+            identifiers += 1;
+            if length <= 2 {
+                short_identifiers += 1;
+            }
+            rest = &rest[start + length..];
+        }
+
+        (identifiers > 0).then(|| short_identifiers as f64 / identifiers as f64)
     }
 
     fn filenames() -> HashSet<&'static str> {
@@ -65,6 +241,16 @@ impl Generated {
     }
 }
 
+impl Classifier for Generated {
+    fn classify_no_read(&self, filepath: &Path) -> Option<bool> {
+        self.is_generated_no_read(filepath).then_some(true)
+    }
+
+    fn classify(&self, filepath: &Path, contents: &[u8]) -> bool {
+        self.is_generated_no_read(filepath) || self.is_generated_with_read(filepath, contents)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -87,16 +273,130 @@ mod tests {
         case("gradlew.bat", true)
     )]
     fn test_is_generated_no_read(filepath: &str, expected: bool) {
-        let generated = Generated::new();
+        let generated = Generated::new(Default::default());
         assert_eq!(generated.is_generated_no_read(filepath), expected);
     }
 
     #[test]
     fn test_likely_minified() {
-        let generated = Generated::new();
+        let generated = Generated::new(Default::default());
         let header: Vec<u8> = b"/*!\n  * This is my license etc etc\n */".to_vec();
         let contents = b"console.log('hello, world!');".repeat(50);
         let contents = [header, contents].concat();
-        assert!(generated.likely_minified(&contents));
+        assert!(generated.likely_minified("hello.js", &contents));
+    }
+
+    #[rstest(
+        filepath,
+        contents,
+        expected,
+        case::minified_after_header(
+            "lib.js",
+            concat!(
+                "/*! lib v1.0 */\n",
+                "!function(e,t){var n=t(e),r=n.a,o=n.b;for(var i=0;i<r;i++)o[i]=e[i]*n.c;",
+                "return{x:r,y:o,z:function(a,b){return a+b+n.d}}}(window,function(e){",
+                "return{a:e.length,b:[],c:2,d:e.x||0}});var a=1,b=2,c=a+b,d=c*a;",
+            ),
+            true
+        ),
+        case::source_map_trailer(
+            "bundle.js",
+            "export function add(a, b) {\n  return a + b;\n}\n//# sourceMappingURL=add.js.map\n",
+            true
+        ),
+        case::webpack_bootstrap(
+            "bundle.js",
+            "/******/ (() => { // webpackBootstrap\n/******/ \tvar __webpack_modules__ = ({});\n",
+            true
+        ),
+        case::esbuild_helpers(
+            "bundle.js",
+            "var __defProp = Object.defineProperty;\nvar __export = (target, all) => {};\n",
+            true
+        ),
+        case::license_banner(
+            "bundle.js",
+            concat!(
+                "// Copyright (c) Example Corporation. Licensed under the MIT license. Permission is hereby granted, free of charge, to any person obtaining a copy of this software and associated documentation files, to deal in the Software without restriction.\n",
+                "\n",
+                "function greet(name) {\n",
+                "    const greeting = `Hello, ${name}!`;\n",
+                "    console.log(greeting);\n",
+                "    return greeting;\n",
+                "}\n",
+            ),
+            false
+        ),
+        case::long_data_literal(
+            "data.js",
+            concat!(
+                "const data = [\"alpha\", \"bravo\", \"charlie\", \"delta\", \"echo\", \"foxtrot\", \"golf\", \"hotel\", \"india\", \"juliett\", \"kilo\", \"lima\", \"mike\", \"november\", \"oscar\", \"papa\", \"quebec\", \"romeo\", \"sierra\", \"tango\", \"uniform\"];\n",
+                "\n",
+                "export function lookup(index) {\n",
+                "    return data[index];\n",
+                "}\n",
+            ),
+            false
+        ),
+        case::webpack_runtime(
+            "bundle.js",
+            "var x = 1;\n__webpack_require__.r(__webpack_exports__);\n",
+            true
+        ),
+        case::esbuild_interop(
+            "bundle.mjs",
+            "var __toESM = (mod, isNodeMode, target) => mod;\n",
+            true
+        ),
+        case::parcel_prelude(
+            "bundle.js",
+            "parcelRequire = (function (modules, cache, entry, globalName) {\n",
+            true
+        ),
+        case::mentioned_in_javascript(
+            "loader.js",
+            "// Unlike __webpack_require__, this uses __toESM( and parcelRequire.\nexport const load = (id) => import(id);\n",
+            false
+        ),
+        case::webpack_mentioned_in_rust(
+            "src/generated.rs",
+            "const SIGNATURE: &str = r\"__webpack_require__\";\n",
+            false
+        ),
+        case::signatures_mentioned_in_markdown(
+            "docs/bundlers.md",
+            "# Bundlers\n\nvar __toESM = esbuild's helper.\nparcelRequire = parcel's.\n",
+            false
+        ),
+        case::source_map_mentioned_in_markdown(
+            "docs/source-maps.md",
+            "# Source maps\n\nEnd files with:\n\n//# sourceMappingURL=app.js.map\n",
+            false
+        )
+    )]
+    fn test_likely_minified_scoring(filepath: &str, contents: &str, expected: bool) {
+        let generated = Generated::new(Default::default());
+        assert_eq!(
+            generated.likely_minified(filepath, contents.as_bytes()),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_small_file_is_not_minified() {
+        let generated = Generated::new(Default::default());
+        assert!(!generated.likely_minified("data.json", br#"{"a":1,"b":[2,3]}"#));
+    }
+
+    #[test]
+    fn test_likely_minified_thresholds() {
+        let thresholds = MinificationThresholds {
+            min_score: 4,
+            ..Default::default()
+        };
+        let generated = Generated::new(thresholds);
+        let contents = b"console.log('hello, world!');".repeat(50);
+        assert!(!generated.likely_minified("hello.js", &contents));
     }
 }
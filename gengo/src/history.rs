@@ -0,0 +1,237 @@
+//! Analyzes how the languages of a git repository changed over its history.
+use crate::config::Config;
+use crate::memo::Memo;
+use crate::{Analysis, Error, ErrorKind, FileSource, Gengo, Git, GitBuilder};
+use gix::ObjectId;
+use gix::date::time::format::SHORT;
+use gix::discover::Error as DiscoverError;
+use serde::{Serialize, Serializer};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// How commits are sampled from the history.
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Sampling {
+    /// Every `n`th commit, starting with the newest one.
+    Commits(usize),
+    /// The commits that tags point to.
+    Tags,
+    /// The newest commit, and then the newest commit that is at least this
+    /// many seconds older than the previous sample.
+    Interval(u64),
+}
+
+/// Builds a [`History`].
+pub struct HistoryBuilder {
+    path: PathBuf,
+    rev: String,
+    sampling: Sampling,
+    since: Option<String>,
+}
+
+impl HistoryBuilder {
+    /// Samples the first-parent history of a revision. By default, every
+    /// commit is sampled.
+    pub fn new(path: impl AsRef<Path>, rev: &str) -> Self {
+        Self {
+            path: path.as_ref().to_owned(),
+            rev: rev.to_string(),
+            sampling: Sampling::Commits(1),
+            since: None,
+        }
+    }
+
+    /// Sets how commits are sampled.
+    pub fn sampling(mut self, sampling: Sampling) -> Self {
+        self.sampling = sampling;
+        self
+    }
+
+    /// Stops at the first commit that is older than a date. Dates use git's
+    /// syntax, like `2024-01-31` or `2 years ago`.
+    pub fn since(mut self, date: impl Into<String>) -> Self {
+        self.since = Some(date.into());
+        self
+    }
+
+    pub fn build(self) -> crate::Result<History> {
+        let repository = match gix::discover(&self.path) {
+            Ok(r) => r,
+            Err(DiscoverError::Discover(err)) => {
+                return Err(Box::new(Error::with_source(ErrorKind::NoRepository, err)));
+            }
+            Err(err) => return Err(err.into()),
+        };
+        let since = match &self.since {
+            Some(date) => Some(
+                gix::date::parse(date, Some(SystemTime::now()))
+                    .map_err(|err| format!("invalid date {date:?}: {err}"))?
+                    .seconds,
+            ),
+            None => None,
+        };
+
+        // NOTE The newest commit comes first.
+        let mut commits = Vec::new();
+        let mut next = Some(
+            repository
+                .rev_parse_single(self.rev.as_str())?
+                .object()?
+                .peel_to_commit()?
+                .id,
+        );
+        while let Some(id) = next {
+            let commit = repository.find_commit(id)?;
+            let time = commit.time()?.seconds;
+            if since.is_some_and(|since| time < since) {
+                break;
+            }
+            next = commit.parent_ids().next().map(|id| id.detach());
+            commits.push((id, time));
+        }
+
+        let mut tags: HashMap<ObjectId, String> = HashMap::new();
+        for reference in repository.references()?.tags()? {
+            let mut reference = reference.map_err(|err| err as Box<dyn std::error::Error>)?;
+            let name = reference.name().shorten().to_string();
+            // NOTE Tags of trees and blobs can't be sampled.
+            let Ok(commit) = reference.peel_to_commit() else {
+                continue;
+            };
+            tags.entry(commit.id)
+                .and_modify(|tag| {
+                    if name < *tag {
+                        tag.clone_from(&name);
+                    }
+                })
+                .or_insert(name);
+        }
+
+        let sampled: Vec<_> = match self.sampling {
+            Sampling::Commits(n) => commits.into_iter().step_by(n.max(1)).collect(),
+            Sampling::Tags => commits
+                .into_iter()
+                .filter(|(id, _)| tags.contains_key(id))
+                .collect(),
+            Sampling::Interval(seconds) => {
+                let seconds = i64::try_from(seconds).unwrap_or(i64::MAX);
+                let mut until: Option<i64> = None;
+                commits
+                    .into_iter()
+                    .filter(|&(_, time)| {
+                        if until.is_some_and(|until| time > until) {
+                            return false;
+                        }
+                        until = Some(time.saturating_sub(seconds));
+                        true
+                    })
+                    .collect()
+            }
+        };
+        let samples = sampled
+            .into_iter()
+            .rev()
+            .map(|(id, time)| Sample {
+                id,
+                time,
+                tag: tags.get(&id).cloned(),
+            })
+            .collect();
+
+        Ok(History {
+            path: self.path,
+            samples,
+        })
+    }
+}
+
+/// Commits sampled from the first-parent history of a git repository.
+pub struct History {
+    path: PathBuf,
+    samples: Vec<Sample>,
+}
+
+impl History {
+    /// Samples the first-parent history of a revision, every commit.
+    pub fn new(path: impl AsRef<Path>, rev: &str) -> crate::Result<Self> {
+        HistoryBuilder::new(path, rev).build()
+    }
+
+    /// The sampled commits, from oldest to newest.
+    pub fn samples(&self) -> &[Sample] {
+        &self.samples
+    }
+
+    /// Analyzes each sampled commit, from oldest to newest. `build` is called
+    /// with a [`GitBuilder`] for each commit, and should configure each one
+    /// the same way.
+    ///
+    /// Results are reused for blobs whose ID, path, and overrides didn't
+    /// change since an earlier sample, so only the changed blobs are read.
+    /// They're forgotten when the configuration file changes.
+    pub fn analyze<F>(&self, mut build: F) -> crate::Result<Vec<(Sample, Analysis)>>
+    where
+        F: FnMut(GitBuilder) -> crate::Result<Gengo<Git>>,
+    {
+        let memo = Memo::default();
+        let mut config = None;
+        let mut analyses = Vec::with_capacity(self.samples.len());
+        for sample in &self.samples {
+            let gengo = build(GitBuilder::new(&self.path, &sample.id()))?;
+            let sample_config = gengo
+                .file_source
+                .read_root_file(Path::new(Config::FILENAME))?;
+            if sample_config != config {
+                memo.clear();
+                config = sample_config;
+            }
+            let analysis = gengo.analyze_with(Some(&memo))?;
+            analyses.push((sample.clone(), analysis));
+        }
+        Ok(analyses)
+    }
+}
+
+/// A sampled commit.
+#[derive(Clone, Debug, Serialize)]
+pub struct Sample {
+    #[serde(serialize_with = "serialize_id")]
+    id: ObjectId,
+    time: i64,
+    tag: Option<String>,
+}
+
+impl Sample {
+    /// The hex ID of the commit.
+    pub fn id(&self) -> String {
+        self.id.to_string()
+    }
+
+    /// The abbreviated hex ID of the commit, like `git log --oneline` shows.
+    pub fn short_id(&self) -> String {
+        self.id.to_hex_with_len(7).to_string()
+    }
+
+    /// When the commit was committed, in seconds since the Unix epoch.
+    pub fn time(&self) -> i64 {
+        self.time
+    }
+
+    /// The date that the commit was committed, like `2024-01-31`, in UTC.
+    pub fn date(&self) -> String {
+        gix::date::Time::new(self.time, 0).format_or_unix(SHORT)
+    }
+
+    /// The name of a tag that points to the commit. If several do, the first
+    /// one by name is used.
+    pub fn tag(&self) -> Option<&str> {
+        self.tag.as_deref()
+    }
+}
+
+/// Serializes a commit ID as hex.
+fn serialize_id<S: Serializer>(id: &ObjectId, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(id)
+}
@@ -29,41 +29,58 @@
 //! [github-linguist]: https://github.com/github-linguist/linguist
 
 pub use analysis::Analysis;
+use analysis::{Diagnostic, Stage};
 use binary::Binary;
+pub use binary::BinaryFormat;
 pub use builder::Builder;
+pub use classifier::Classifier;
+use config::Config;
+use contents::LazyContents;
+pub use detectability::DetectabilityPolicy;
 use documentation::Documentation;
 
 pub use error::{Error, ErrorKind};
 use generated::Generated;
+pub use generated::MinificationThresholds;
 
 #[cfg(feature = "directory")]
-pub use file_source::Directory;
+pub use file_source::{Directory, DirectoryBuilder};
 
 #[cfg(feature = "git")]
-pub use file_source::Git;
+pub use file_source::{Git, GitBuilder, Submodules};
+#[cfg(feature = "git")]
+pub use history::{History, HistoryBuilder, Sample, Sampling};
 
-pub use file_source::FileSource;
-use glob::MatchOptions;
-use indexmap::IndexMap;
-use language::Category;
+pub use file_source::{FileSource, Lfs, Overrides, SymlinkPolicy};
+use glob::{MatchOptions, Pattern};
 pub use language::Language;
+use memo::Memo;
+use overrides::OverrideRules;
 
 use std::error::Error as ErrorTrait;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, PoisonError};
 
 use vendored::Vendored;
 
-use rayon::prelude::{FromParallelIterator, ParallelBridge, ParallelIterator};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 pub mod analysis;
 mod binary;
 mod builder;
+mod classifier;
+mod config;
+mod contents;
+mod detectability;
 mod documentation;
 mod error;
 mod file_source;
 mod generated;
+#[cfg(feature = "git")]
+mod history;
 pub mod language;
+mod memo;
+mod overrides;
 mod vendored;
 
 type GenericError = Box<dyn ErrorTrait>;
@@ -84,107 +101,353 @@ pub struct Gengo<FS: for<'fs> FileSource<'fs>> {
     documentation: Documentation,
     generated: Generated,
     vendored: Vendored,
+    classifiers: Vec<(String, Box<dyn Classifier>)>,
+    override_rules: OverrideRules,
+    detectability: DetectabilityPolicy,
+    ignore: Vec<Pattern>,
 }
 
 impl<FS: for<'fs> FileSource<'fs>> Gengo<FS> {
     /// Analyzes each file in the repository at the given revision.
+    ///
+    /// Files that can't be read are skipped and reported in
+    /// [`Analysis::diagnostics`].
     pub fn analyze(&self) -> Result<Analysis> {
+        self.analyze_with(None)
+    }
+
+    /// Analyzes each file, reusing the results in `memo` for files that the
+    /// file source can identify, and recording the new ones.
+    pub(crate) fn analyze_with(&self, memo: Option<&Memo>) -> Result<Analysis> {
         let state = self.file_source.state()?;
-        let entries = self
+        let results = Mutex::new((Vec::new(), Vec::new()));
+        self.file_source.visit_entries(state, |state, entry| {
+            let result = entry.and_then(|entry| self.analyze_entry(entry, state, memo));
+            let mut results = results.lock().unwrap_or_else(PoisonError::into_inner);
+            match result {
+                Ok(Some(entry)) => results.0.push(entry),
+                Ok(None) => (),
+                Err(diagnostic) => results.1.push(diagnostic),
+            }
+        })?;
+        let (entries, diagnostics) = results.into_inner().unwrap_or_else(PoisonError::into_inner);
+        let entries = entries.into_iter().collect();
+
+        Ok(Analysis {
+            entries,
+            diagnostics,
+        })
+    }
+
+    /// Analyzes a single entry. Returns `None` if the file should not be
+    /// included in the analysis.
+    fn analyze_entry<'fs>(
+        &'fs self,
+        entry: <FS as FileSource<'fs>>::Entry,
+        state: &mut <FS as FileSource<'fs>>::State,
+        memo: Option<&Memo>,
+    ) -> Result<Option<(PathBuf, Entry)>, Diagnostic> {
+        let filepath = self
             .file_source
-            .entries()?
-            .par_bridge()
-            .map_with(state, |state, entry| {
-                let filepath = self.file_source.filepath(&entry, state).ok()?;
-                let contents = self.file_source.contents(&entry, state).ok()?;
+            .filepath(&entry, state)
+            .map_err(|err| Diagnostic::from_error(None, Stage::Filepath, err.as_ref()))?;
+        let filepath = filepath.as_ref();
+        // NOTE The configuration file is left out like other hidden files, even
+        //      by file sources that don't hide them.
+        if filepath == Path::new(Config::FILENAME) || config::matches_any(&self.ignore, filepath) {
+            return Ok(None);
+        }
+        let mut overrides = self.file_source.overrides(filepath, state);
+        self.override_rules.apply(filepath, &mut overrides);
+        let key = memo.and_then(|_| {
+            let content_id = self.file_source.content_id(&entry, state)?;
+            Some(memo::Key::new(
+                content_id,
+                filepath.to_owned(),
+                overrides.clone(),
+            ))
+        });
+        if let (Some(memo), Some(key)) = (memo, &key)
+            && let Some(entry) = memo.get(key)
+        {
+            return Ok(entry.map(|entry| (filepath.to_owned(), entry)));
+        }
+        let diagnostic = |stage, err: GenericError| {
+            Diagnostic::from_error(Some(filepath.to_owned()), stage, err.as_ref())
+        };
 
-                let entry = self.analyze_blob(&filepath, contents, state)?;
-                Some((filepath.as_ref().to_owned(), entry))
-            })
-            .filter_map(|entry| entry);
-        let entries = IndexMap::from_par_iter(entries);
+        let lfs = self
+            .file_source
+            .lfs(&entry, state)
+            .map_err(|err| diagnostic(Stage::Contents, err))?;
+        let size = match lfs {
+            Some(lfs) => Some(lfs.size),
+            None => self
+                .file_source
+                .size(&entry, state)
+                .map_err(|err| diagnostic(Stage::Size, err))?,
+        };
+        let mut contents = LazyContents::new(&self.file_source, &entry, state, size);
+        if lfs.is_some_and(|lfs| !lfs.available) {
+            // NOTE Only the pointer is available, so only the path is used.
+            contents = contents.unavailable();
+        }
+        // NOTE Fall back to the size of the contents if the file source
+        //      doesn't know the real size.
+        let size = match size {
+            Some(size) => usize::try_from(size).unwrap_or(usize::MAX),
+            None => contents
+                .get(self.read_limit)
+                .map_err(|err| diagnostic(Stage::Contents, err))?
+                .len(),
+        };
 
-        Ok(Analysis(entries))
+        let blob = self
+            .analyze_blob(filepath, &mut contents, size, overrides)
+            .map_err(|err| diagnostic(Stage::Contents, err))?;
+        // NOTE The weight only replaces the size once the file was
+        //      classified, since checks like the generated check need the
+        //      real size.
+        let weight = match (&blob, lfs) {
+            (Some(_), None) => self
+                .file_source
+                .weight(&entry, state)
+                .map_err(|err| diagnostic(Stage::Size, err))?,
+            _ => None,
+        };
+        let symlink = self.file_source.is_symlink(&entry, state);
+        let entry = blob.map(|blob| Entry {
+            size: weight.map_or(blob.size, |weight| {
+                usize::try_from(weight).unwrap_or(usize::MAX)
+            }),
+            lfs: lfs.is_some(),
+            symlink,
+            ..blob
+        });
+        if let (Some(memo), Some(key)) = (memo, key) {
+            memo.insert(key, entry.clone());
+        }
+        Ok(entry.map(|entry| (filepath.to_owned(), entry)))
     }
 
-    fn analyze_blob(
+    /// Analyzes a file. `size` is the real size of the file. The contents are
+    /// only read when a check needs them, and only as much as it needs.
+    fn analyze_blob<'fs>(
         &self,
-        filepath: impl AsRef<Path>,
-        contents: impl AsRef<[u8]>,
-        state: &mut <FS as FileSource>::State,
-    ) -> Option<Entry> {
-        let overrides = self.file_source.overrides(&filepath, state);
-        let filepath = filepath.as_ref();
-        let contents = contents.as_ref();
-
-        // NOTE Users might be surprised if there is an override for a binary file but it
-        //      is still skipped, but this should be a rare case.
-        if self.is_binary(filepath, contents) {
-            return None;
+        filepath: &Path,
+        contents: &mut LazyContents<'_, 'fs, FS>,
+        size: usize,
+        overrides: Overrides,
+    ) -> Result<Option<Entry>> {
+        let binary = match overrides.is_binary {
+            Some(binary) => binary,
+            None => self.classify(&self.binary, filepath, contents, self.binary.sniff_len())?,
+        };
+        if binary {
+            return self
+                .analyze_binary(filepath, contents, size, overrides)
+                .map(Some);
         }
 
+        let language = match overrides.language {
+            Some(language) => language,
+            None => {
+                let head = contents.get(Language::SHEBANG_LEN)?;
+                match Language::candidates(filepath, head)[..] {
+                    [] => return Ok(None),
+                    [language] => language,
+                    // NOTE Only heuristics need more than the first bytes.
+                    _ => {
+                        let contents = contents.get(self.read_limit)?;
+                        let Some(language) = Language::pick(filepath, contents, self.read_limit)
+                        else {
+                            return Ok(None);
+                        };
+                        language
+                    }
+                }
+            }
+        };
+        let generated = match overrides.is_generated {
+            Some(generated) => generated,
+            None => self.classify_generated(filepath, contents, size)?,
+        };
+        let documentation = match overrides.is_documentation {
+            Some(documentation) => documentation,
+            None => self.classify(&self.documentation, filepath, contents, self.read_limit)?,
+        };
+        let vendored = match overrides.is_vendored {
+            Some(vendored) => vendored,
+            None => self.classify(&self.vendored, filepath, contents, self.read_limit)?,
+        };
+
+        let detectable = overrides.is_detectable.unwrap_or_else(|| {
+            self.detectability
+                .is_detectable(&language, generated, documentation, vendored)
+        });
+
+        let flags = self.custom_flags(filepath, contents)?;
+        let truncated = contents.truncated(size, self.read_limit);
+        let entry = Entry {
+            language: Some(language),
+            size,
+            truncated,
+            detectable,
+            generated,
+            documentation,
+            vendored,
+            binary: false,
+            binary_format: None,
+            lfs: false,
+            symlink: false,
+            flags,
+        };
+        Ok(Some(entry))
+    }
+
+    /// Analyzes a binary file. Binary files are never detectable unless
+    /// overridden, and the built-in checks only use their filepath. The
+    /// contents are only read to identify the format by its magic number, and
+    /// for custom classifiers that need them.
+    fn analyze_binary<'fs>(
+        &self,
+        filepath: &Path,
+        contents: &mut LazyContents<'_, 'fs, FS>,
+        size: usize,
+        overrides: Overrides,
+    ) -> Result<Entry> {
         let language = overrides
             .language
-            .or_else(|| Language::pick(filepath, contents, self.read_limit))?;
+            .or_else(|| Language::pick(filepath, &[], self.read_limit));
         let generated = overrides
             .is_generated
-            .unwrap_or_else(|| self.is_generated(filepath, contents));
+            .unwrap_or_else(|| self.is_generated(filepath, &[]));
         let documentation = overrides
             .is_documentation
-            .unwrap_or_else(|| self.is_documentation(filepath, contents));
+            .unwrap_or_else(|| self.is_documentation(filepath, &[]));
         let vendored = overrides
             .is_vendored
-            .unwrap_or_else(|| self.is_vendored(filepath, contents));
-
-        let detectable = match language.category() {
-            Category::Data | Category::Prose => false,
-            Category::Pattern | Category::Programming | Category::Markup | Category::Query => {
-                !(generated || documentation || vendored)
-            }
-            category => unimplemented!("Failed to check if category {category:?} is detectable"),
-        };
-        let detectable = overrides.is_detectable.unwrap_or(detectable);
+            .unwrap_or_else(|| self.is_vendored(filepath, &[]));
+        let detectable = overrides.is_detectable.unwrap_or(false);
 
-        let size = contents.len();
-        let entry = Entry {
+        // NOTE Unavailable contents are empty, so only the extension is used.
+        let head = contents.get(Binary::MAGIC_LEN.min(self.read_limit))?;
+        let binary_format = self.binary_format(filepath, head);
+        let flags = self.custom_flags(filepath, contents)?;
+        let truncated = contents.truncated(size, self.read_limit);
+        Ok(Entry {
             language,
             size,
+            truncated,
             detectable,
             generated,
             documentation,
             vendored,
-        };
-        Some(entry)
+            binary: true,
+            binary_format,
+            lfs: false,
+            symlink: false,
+            flags,
+        })
+    }
+
+    /// Runs a classifier, only reading up to `len` bytes of the contents if
+    /// the filepath isn't enough.
+    fn classify<'fs>(
+        &self,
+        classifier: &dyn Classifier,
+        filepath: &Path,
+        contents: &mut LazyContents<'_, 'fs, FS>,
+        len: usize,
+    ) -> Result<bool> {
+        match classifier.classify_no_read(filepath) {
+            Some(classified) => Ok(classified),
+            None => Ok(classifier.classify(filepath, contents.get(len)?)),
+        }
+    }
+
+    /// Checks if a file is generated. Only the start of the file that is
+    /// scored is read, and the end of the file if it may have a source map
+    /// trailer.
+    fn classify_generated<'fs>(
+        &self,
+        filepath: &Path,
+        contents: &mut LazyContents<'_, 'fs, FS>,
+        size: usize,
+    ) -> Result<bool> {
+        let window = self.generated.window().min(self.read_limit);
+        if self.classify(&self.generated, filepath, contents, window)? {
+            return Ok(true);
+        }
+        // NOTE The whole file was checked if it fits in the window.
+        if !Generated::may_be_bundled(filepath) || size <= window {
+            return Ok(false);
+        }
+        let mut len = Generated::TRAILER_LEN.min(self.read_limit);
+        loop {
+            let tail = contents.tail(len)?;
+            if tail.len() < len || len == self.read_limit || Generated::has_trailing_lines(tail) {
+                return Ok(Generated::has_source_map_trailer(tail));
+            }
+            len = len.saturating_mul(2).min(self.read_limit);
+        }
+    }
+
+    /// Gets the names of the custom classifiers that flag the file.
+    fn custom_flags<'fs>(
+        &self,
+        filepath: &Path,
+        contents: &mut LazyContents<'_, 'fs, FS>,
+    ) -> Result<Vec<String>> {
+        let mut flags = Vec::new();
+        for (name, classifier) in &self.classifiers {
+            if self.classify(classifier.as_ref(), filepath, contents, self.read_limit)? {
+                flags.push(name.clone());
+            }
+        }
+        Ok(flags)
     }
 
     /// Guesses if a file is generated.
     pub fn is_generated(&self, filepath: impl AsRef<Path>, contents: &[u8]) -> bool {
-        self.generated.is_generated(filepath, contents)
+        classifier::run(&self.generated, filepath.as_ref(), contents)
     }
 
     /// Guesses if a file is documentation.
     pub fn is_documentation(&self, filepath: impl AsRef<Path>, contents: &[u8]) -> bool {
-        self.documentation.is_documentation(filepath, contents)
+        classifier::run(&self.documentation, filepath.as_ref(), contents)
     }
 
     /// Guesses if a file is vendored.
     pub fn is_vendored(&self, filepath: impl AsRef<Path>, contents: &[u8]) -> bool {
-        self.vendored.is_vendored(filepath, contents)
+        classifier::run(&self.vendored, filepath.as_ref(), contents)
     }
 
     /// Guesses if a file is binary.
     pub fn is_binary(&self, filepath: impl AsRef<Path>, contents: &[u8]) -> bool {
-        self.binary.is_binary(filepath, contents)
+        classifier::run(&self.binary, filepath.as_ref(), contents)
+    }
+
+    /// Identifies the format of a binary file.
+    pub fn binary_format(
+        &self,
+        filepath: impl AsRef<Path>,
+        contents: &[u8],
+    ) -> Option<BinaryFormat> {
+        self.binary.identify(filepath, contents)
     }
 }
 
 /// A single entry in the language statistics.
-#[derive(Debug, Serialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Entry {
     /// The detected language.
-    language: Language,
+    language: Option<Language>,
     /// The size of the file.
     size: usize,
+    /// If only part of the file's contents were used for detection.
+    #[serde(default)]
+    truncated: bool,
     /// If the file is detectable (should not be ignored).
     detectable: bool,
     /// If the file was generated.
@@ -193,19 +456,54 @@ pub struct Entry {
     documentation: bool,
     /// If the file is vendored.
     vendored: bool,
+    /// If the file is binary.
+    #[serde(default)]
+    binary: bool,
+    /// The format of the file, if it is binary and the format was identified.
+    #[serde(default)]
+    binary_format: Option<BinaryFormat>,
+    /// If the file is stored in Git LFS.
+    #[serde(default)]
+    lfs: bool,
+    /// If the file is a symbolic link.
+    #[serde(default)]
+    symlink: bool,
+    /// The names of the custom classifiers that flagged the file.
+    #[serde(default)]
+    flags: Vec<String>,
 }
 
 impl Entry {
-    /// The detected language.
-    pub fn language(&self) -> &Language {
-        &self.language
+    /// Checks if two entries were classified the same way, ignoring their
+    /// sizes.
+    pub(crate) fn same_classification(&self, other: &Self) -> bool {
+        self.language == other.language
+            && self.detectable == other.detectable
+            && self.generated == other.generated
+            && self.documentation == other.documentation
+            && self.vendored == other.vendored
+            && self.binary == other.binary
+            && self.binary_format == other.binary_format
+            && self.flags == other.flags
     }
 
-    /// The size of the file.
+    /// The detected language. Only binary files can be missing a language.
+    pub fn language(&self) -> Option<&Language> {
+        self.language.as_ref()
+    }
+
+    /// The size of the file. This is the real size of the file, even if only
+    /// part of it was read.
     pub fn size(&self) -> usize {
         self.size
     }
 
+    /// If only part of the file's contents were used for detection, because
+    /// the file is larger than the read limit.
+    pub fn truncated(&self) -> bool {
+        self.truncated
+    }
+
     /// If the file is detectable (should not be ignored).
     pub fn detectable(&self) -> bool {
         self.detectable
@@ -225,4 +523,37 @@ impl Entry {
     pub fn vendored(&self) -> bool {
         self.vendored
     }
+
+    /// If the file is binary.
+    pub fn binary(&self) -> bool {
+        self.binary
+    }
+
+    /// The format of the file, if it is binary and the format was identified.
+    pub fn binary_format(&self) -> Option<BinaryFormat> {
+        self.binary_format
+    }
+
+    /// If the file is stored in Git LFS. If its real contents weren't
+    /// available, it was classified by its path only.
+    pub fn lfs(&self) -> bool {
+        self.lfs
+    }
+
+    /// If the file is a symbolic link. Depending on the file source's
+    /// [`SymlinkPolicy`], it was analyzed as the path of its target, or as
+    /// its target.
+    pub fn symlink(&self) -> bool {
+        self.symlink
+    }
+
+    /// The names of the custom classifiers that flagged the file.
+    pub fn flags(&self) -> &[String] {
+        &self.flags
+    }
+
+    /// If a custom classifier with the given name flagged the file.
+    pub fn has_flag(&self, name: &str) -> bool {
+        self.flags.iter().any(|flag| flag == name)
+    }
 }
@@ -0,0 +1,59 @@
+use crate::{Entry, Overrides};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Mutex, PoisonError};
+
+/// Results of files that were already analyzed, so that analyzing another
+/// revision only reads the files that changed.
+#[derive(Default)]
+pub struct Memo {
+    results: Mutex<HashMap<Key, Option<Entry>>>,
+}
+
+/// Identifies a result. The path and overrides are part of the key, since the
+/// same contents can be classified differently at another path.
+#[derive(Eq, Hash, PartialEq)]
+pub struct Key {
+    content_id: Vec<u8>,
+    path: PathBuf,
+    overrides: Overrides,
+}
+
+impl Key {
+    pub fn new(content_id: Vec<u8>, path: PathBuf, overrides: Overrides) -> Self {
+        Self {
+            content_id,
+            path,
+            overrides,
+        }
+    }
+}
+
+impl Memo {
+    /// Gets the result for a key. The outer `None` means that the key hasn't
+    /// been analyzed, and the inner `None` means that it was left out of the
+    /// analysis.
+    pub fn get(&self, key: &Key) -> Option<Option<Entry>> {
+        self.results
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(key)
+            .cloned()
+    }
+
+    pub fn insert(&self, key: Key, result: Option<Entry>) {
+        self.results
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(key, result);
+    }
+
+    /// Forgets every result, like when the configuration changed.
+    #[cfg(feature = "git")]
+    pub fn clear(&self) {
+        self.results
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clear();
+    }
+}
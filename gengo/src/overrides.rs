@@ -0,0 +1,65 @@
+use super::GLOB_MATCH_OPTIONS;
+use crate::Overrides;
+use glob::{Pattern, PatternError};
+use std::path::Path;
+
+/// Ordered glob rules that override a file's detected attributes.
+#[derive(Default)]
+pub struct OverrideRules {
+    rules: Vec<(Pattern, Overrides)>,
+}
+
+impl OverrideRules {
+    pub fn new<S: AsRef<str>>(
+        rules: impl IntoIterator<Item = (S, Overrides)>,
+    ) -> Result<Self, PatternError> {
+        let rules = rules
+            .into_iter()
+            .map(|(glob, overrides)| Ok((Pattern::new(glob.as_ref())?, overrides)))
+            .collect::<Result<_, _>>()?;
+        Ok(Self { rules })
+    }
+
+    /// Applies each matching rule in order, so that later rules take precedence.
+    pub fn apply(&self, filepath: impl AsRef<Path>, overrides: &mut Overrides) {
+        let filepath = filepath.as_ref();
+        self.rules
+            .iter()
+            .filter(|(glob, _)| glob.matches_path_with(filepath, GLOB_MATCH_OPTIONS))
+            .for_each(|(_, rule)| overrides.overlay(rule));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Language;
+
+    #[test]
+    fn test_later_rules_take_precedence() {
+        let generated = Overrides {
+            is_generated: Some(true),
+            language: Some(Language::PlainText),
+            ..Default::default()
+        };
+        let not_generated = Overrides {
+            is_generated: Some(false),
+            ..Default::default()
+        };
+        let rules =
+            OverrideRules::new([("dist/**", generated), ("dist/keep/*", not_generated)]).unwrap();
+
+        let mut overrides = Overrides::default();
+        rules.apply("dist/keep/main.js", &mut overrides);
+        assert_eq!(overrides.is_generated, Some(false));
+        assert_eq!(overrides.language, Some(Language::PlainText));
+
+        let mut overrides = Overrides::default();
+        rules.apply("dist/main.js", &mut overrides);
+        assert_eq!(overrides.is_generated, Some(true));
+
+        let mut overrides = Overrides::default();
+        rules.apply("src/main.js", &mut overrides);
+        assert_eq!(overrides, Overrides::default());
+    }
+}
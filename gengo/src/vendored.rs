@@ -1,4 +1,5 @@
 use super::GLOB_MATCH_OPTIONS;
+use crate::Classifier;
 use glob::Pattern;
 use std::path::Path;
 
@@ -13,8 +14,10 @@ impl Vendored {
         Self { globs }
     }
 
-    pub fn is_vendored(&self, filepath: impl AsRef<Path>, contents: &[u8]) -> bool {
-        self.is_vendored_no_read(&filepath) || self.is_vendored_with_read(&filepath, contents)
+    /// Adds globs that match files in addition to the built-in globs.
+    pub fn with_globs(mut self, globs: impl IntoIterator<Item = Pattern>) -> Self {
+        self.globs.extend(globs);
+        self
     }
 
     fn is_vendored_no_read(&self, filepath: impl AsRef<Path>) -> bool {
@@ -23,10 +26,6 @@ impl Vendored {
             .any(|g| g.matches_path_with(filepath.as_ref(), GLOB_MATCH_OPTIONS))
     }
 
-    fn is_vendored_with_read(&self, _filepath: impl AsRef<Path>, _contents: &[u8]) -> bool {
-        false
-    }
-
     fn globs() -> Vec<Pattern> {
         ["**/node_modules/**", "**/tests/fixtures/**"]
             .into_iter()
@@ -35,6 +34,17 @@ impl Vendored {
     }
 }
 
+impl Classifier for Vendored {
+    // NOTE Vendored files are currently only detected by the filepath.
+    fn classify_no_read(&self, filepath: &Path) -> Option<bool> {
+        Some(self.is_vendored_no_read(filepath))
+    }
+
+    fn classify(&self, filepath: &Path, _contents: &[u8]) -> bool {
+        self.is_vendored_no_read(filepath)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
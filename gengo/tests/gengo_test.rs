@@ -1,9 +1,17 @@
-#![cfg(feature = "git")]
-use gengo::{Builder, Git};
+use gengo::Builder;
+#[cfg(feature = "git")]
+use gengo::Git;
+#[cfg(feature = "directory")]
+use gengo::{Classifier, Directory, DirectoryBuilder, analysis::SummaryOpts};
+use std::path::Path;
+use support::{entry, find};
+
+mod support;
 
 const ROOT: &str = env!("CARGO_MANIFEST_DIR");
 
 #[test]
+#[cfg(feature = "git")]
 fn test_git_javascript() {
     // TODO It's not great to use a snapshot test on a type that doesn't
     // guarantee order. Improve this test.
@@ -14,3 +22,1069 @@ fn test_git_javascript() {
     results.sort_by_key(|(path, _)| path.to_owned());
     insta::assert_debug_snapshot!(results);
 }
+
+#[cfg(feature = "directory")]
+struct Benchmark;
+
+#[cfg(feature = "directory")]
+impl Classifier for Benchmark {
+    fn classify_no_read(&self, filepath: &Path) -> Option<bool> {
+        Some(filepath.components().any(|c| c.as_os_str() == "benches"))
+    }
+
+    fn classify(&self, filepath: &Path, _contents: &[u8]) -> bool {
+        self.classify_no_read(filepath).unwrap()
+    }
+}
+
+#[test]
+#[cfg(feature = "directory")]
+fn test_custom_classifier() {
+    let directory = Directory::new(ROOT, Builder::<Directory>::DEFAULT_READ_LIMIT).unwrap();
+    let gengo = Builder::new(directory)
+        .classifier("benchmark", Benchmark)
+        .build()
+        .unwrap();
+    let analysis = gengo.analyze().unwrap();
+
+    let (flagged, unflagged): (Vec<_>, Vec<_>) = analysis
+        .iter()
+        .partition(|(_, entry)| entry.has_flag("benchmark"));
+    assert!(flagged.iter().all(|(path, _)| path.starts_with("benches")));
+    assert!(!flagged.is_empty());
+    assert!(!unflagged.is_empty());
+
+    let mut opts = SummaryOpts::default();
+    opts.with_flags = vec!["benchmark".into()];
+    let benchmarks = analysis.summary_with(opts);
+    let mut opts = SummaryOpts::default();
+    opts.without_flags = vec!["benchmark".into()];
+    let others = analysis.summary_with(opts);
+    assert_eq!(
+        benchmarks.total() + others.total(),
+        analysis.summary_with(Default::default()).total()
+    );
+}
+
+#[test]
+#[cfg(feature = "directory")]
+fn test_custom_classifier_on_binary_files() {
+    /// Flags PNG images by their contents.
+    struct Png;
+
+    impl Classifier for Png {
+        fn classify(&self, _filepath: &Path, contents: &[u8]) -> bool {
+            contents.starts_with(b"\x89PNG\r\n\x1a\n")
+        }
+    }
+
+    let root = support::directory(&[
+        ("image.dat", b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR"),
+        ("other.dat", b"\0\0\0\0\0\0\0\0\0\0\0\0\0"),
+    ]);
+
+    let directory = Directory::new(root.path(), Builder::<Directory>::DEFAULT_READ_LIMIT).unwrap();
+    let gengo = Builder::new(directory)
+        .classifier("png", Png)
+        .build()
+        .unwrap();
+    let analysis = gengo.analyze().unwrap();
+
+    let image = entry(&analysis, "image.dat");
+    assert!(image.binary());
+    assert!(image.has_flag("png"));
+    let other = entry(&analysis, "other.dat");
+    assert!(other.binary());
+    assert!(!other.has_flag("png"));
+}
+
+#[test]
+#[cfg(feature = "directory")]
+fn test_directory_gitattributes() {
+    use gengo::Language;
+
+    let root = support::directory(&[
+        (
+            ".gitattributes",
+            b"[attr]third-party gengo-vendored\n*.txt gengo-language=Markdown\n",
+        ),
+        ("lib/.gitattributes", b"extern/** third-party\n"),
+        ("notes.txt", b"# Notes\n"),
+        ("lib/main.rs", b"fn main() {}\n"),
+        ("lib/extern/lib.rs", b"pub fn lib() {}\n"),
+    ]);
+
+    let directory = Directory::new(root.path(), Builder::<Directory>::DEFAULT_READ_LIMIT).unwrap();
+    let gengo = Builder::new(directory).build().unwrap();
+    let analysis = gengo.analyze().unwrap();
+
+    assert_eq!(
+        entry(&analysis, "notes.txt").language(),
+        Some(&Language::Markdown)
+    );
+    assert!(entry(&analysis, "lib/extern/lib.rs").vendored());
+    assert!(!entry(&analysis, "lib/main.rs").vendored());
+}
+
+#[test]
+#[cfg(feature = "directory")]
+fn test_directory_builder() {
+    let root = support::directory(&[
+        (".gengoignore", b"*.tmp.rs\n"),
+        (".github/workflows/ci.yml", b"on: push\n"),
+        ("src/lib.rs", b"pub fn lib() {}\n"),
+        ("src/scratch.tmp.rs", b"fn tmp() {}\n"),
+        ("src/nested/mod.rs", b"pub fn nested() {}\n"),
+        ("src/nested/test.rs", b"fn test() {}\n"),
+    ]);
+
+    let paths = |builder: DirectoryBuilder| {
+        let gengo = Builder::new(builder.build().unwrap()).build().unwrap();
+        support::paths(&gengo.analyze().unwrap())
+    };
+    let builder = || DirectoryBuilder::new(root.path(), Builder::<Directory>::DEFAULT_READ_LIMIT);
+
+    assert_eq!(
+        paths(builder()),
+        vec!["src/lib.rs", "src/nested/mod.rs", "src/nested/test.rs"]
+    );
+    assert!(paths(builder().hidden(true)).contains(&".github/workflows/ci.yml".to_string()));
+    assert!(paths(builder().gengo_ignore(false)).contains(&"src/scratch.tmp.rs".to_string()));
+    assert_eq!(paths(builder().max_depth(Some(2))), vec!["src/lib.rs"]);
+    assert_eq!(
+        paths(builder().include("src/nested/*").exclude("**/test.rs")),
+        vec!["src/nested/mod.rs"]
+    );
+}
+
+#[test]
+#[cfg(all(feature = "directory", unix))]
+fn test_directory_diagnostics() {
+    use gengo::analysis::{DiagnosticKind, Stage};
+
+    let root = support::directory(&[("main.rs", b"fn main() {}\n"), ("src/lib.rs", b"")]);
+    std::os::unix::fs::symlink("missing.rs", root.path().join("broken.rs")).unwrap();
+    // NOTE Links to directories aren't followed, and aren't errors either.
+    std::os::unix::fs::symlink("src", root.path().join("linked")).unwrap();
+
+    let directory = Directory::new(root.path(), Builder::<Directory>::DEFAULT_READ_LIMIT).unwrap();
+    let gengo = Builder::new(directory).build().unwrap();
+    let analysis = gengo.analyze().unwrap();
+
+    assert_eq!(support::paths(&analysis), ["main.rs", "src/lib.rs"]);
+    let [diagnostic] = analysis.diagnostics() else {
+        panic!("expected 1 diagnostic, got {:?}", analysis.diagnostics());
+    };
+    assert_eq!(diagnostic.path(), Some(Path::new("broken.rs")));
+    assert_eq!(diagnostic.stage(), Stage::Size);
+    assert_eq!(diagnostic.kind(), DiagnosticKind::NotFound);
+}
+
+#[test]
+#[cfg(all(feature = "directory", unix))]
+fn test_directory_symlinks() {
+    use gengo::{DirectoryBuilder, SymlinkPolicy};
+
+    use gengo::analysis::DiagnosticKind;
+
+    let root = support::directory(&[("main.rs", b"fn main() {}\n")]);
+    let outside = support::directory(&[("lib.rs", b"pub fn lib() {}\n")]);
+    std::os::unix::fs::symlink("main.rs", root.path().join("link.rs")).unwrap();
+    std::os::unix::fs::symlink(
+        outside.path().join("lib.rs"),
+        root.path().join("outside.rs"),
+    )
+    .unwrap();
+
+    let analyze = |symlinks| {
+        let directory =
+            DirectoryBuilder::new(root.path(), Builder::<Directory>::DEFAULT_READ_LIMIT)
+                .symlinks(symlinks)
+                .build()
+                .unwrap();
+        let gengo = Builder::new(directory).build().unwrap();
+        gengo.analyze().unwrap()
+    };
+    let link = |analysis: &gengo::Analysis, path| {
+        find(analysis, path).map(|entry| (entry.symlink(), entry.size()))
+    };
+
+    let skipped = analyze(SymlinkPolicy::Skip);
+    assert_eq!(link(&skipped, "link.rs"), None);
+    assert_eq!(link(&skipped, "outside.rs"), None);
+    let text = analyze(SymlinkPolicy::Text);
+    assert_eq!(link(&text, "link.rs"), Some((true, 7)));
+    assert!(link(&text, "outside.rs").is_some());
+    // NOTE Links are only resolved within the directory.
+    let resolved = analyze(SymlinkPolicy::Resolve);
+    assert_eq!(link(&resolved, "link.rs"), Some((true, 13)));
+    assert_eq!(link(&resolved, "outside.rs"), None);
+    let [diagnostic] = resolved.diagnostics() else {
+        panic!("expected 1 diagnostic, got {:?}", resolved.diagnostics());
+    };
+    assert_eq!(diagnostic.path(), Some(Path::new("outside.rs")));
+    assert_eq!(diagnostic.kind(), DiagnosticKind::NotFound);
+}
+
+#[test]
+#[cfg(feature = "directory")]
+fn test_directory_true_sizes() {
+    let large = "SELECT 1;\n".repeat(100);
+    let root = support::directory(&[
+        ("small.sql", b"SELECT 1;\n"),
+        ("large.sql", large.as_bytes()),
+    ]);
+
+    let directory = Directory::new(root.path(), 64).unwrap();
+    let gengo = Builder::new(directory).read_limit(64).build().unwrap();
+    let analysis = gengo.analyze().unwrap();
+
+    let small = entry(&analysis, "small.sql");
+    assert_eq!(small.size(), 10);
+    assert!(!small.truncated());
+    let large = entry(&analysis, "large.sql");
+    assert_eq!(large.size(), 1000);
+    assert!(large.truncated());
+}
+
+#[test]
+#[cfg(feature = "directory")]
+fn test_analysis_diff() {
+    use gengo::analysis::Change;
+
+    let analyze = |files: &[(&str, &[u8])]| {
+        let root = support::directory(files);
+        let directory =
+            Directory::new(root.path(), Builder::<Directory>::DEFAULT_READ_LIMIT).unwrap();
+        Builder::new(directory).build().unwrap().analyze().unwrap()
+    };
+    let old = analyze(&[
+        ("main.rs", b"fn main() {}\n"),
+        ("lib.rs", b"pub fn f() {}\n"),
+        ("unchanged.rs", b"fn g() {}\n"),
+        ("script", b"#!/usr/bin/env python3\n"),
+    ]);
+    let new = analyze(&[
+        ("main.rs", b"fn main() { lib::f(); }\n"),
+        ("unchanged.rs", b"fn g() {}\n"),
+        ("script", b"#!/bin/sh\n"),
+        ("app.py", b"print(1)\n"),
+    ]);
+
+    let diff = old.diff(&new);
+    let languages: Vec<_> = diff
+        .languages()
+        .iter()
+        .map(|diff| (diff.language, diff.files_delta, diff.size_delta))
+        .collect();
+    assert_eq!(
+        languages,
+        [
+            (gengo::Language::Python, 0, -14),
+            (gengo::Language::Rust, -1, -3),
+            (gengo::Language::Shell, 1, 10),
+        ]
+    );
+    assert!(!diff.languages()[2].is_removed());
+    assert!(diff.languages()[2].is_added());
+    let files: Vec<_> = diff
+        .files()
+        .iter()
+        .map(|diff| (diff.path().to_str().unwrap(), diff.change()))
+        .collect();
+    assert_eq!(
+        files,
+        [
+            ("app.py", Change::Added),
+            ("lib.rs", Change::Removed),
+            ("main.rs", Change::Changed),
+            ("script", Change::Reclassified),
+        ]
+    );
+
+    // NOTE Analyses can be saved and compared later.
+    let saved: gengo::Analysis =
+        serde_yaml::from_str(&serde_yaml::to_string(&new).unwrap()).unwrap();
+    assert!(new.diff(&saved).is_empty());
+}
+
+#[test]
+#[cfg(feature = "directory")]
+fn test_analysis_diff_with_old_format() {
+    // NOTE Older versions didn't serialize the fields that were added since.
+    let old = r##"{
+        "main.rs": {
+            "language": {
+                "name": "Rust",
+                "category": "programming",
+                "hex": "#DD3515",
+                "nerd_font_glyph": ""
+            },
+            "size": 13,
+            "detectable": true,
+            "generated": false,
+            "documentation": false,
+            "vendored": false
+        }
+    }"##;
+    let old: gengo::Analysis = serde_yaml::from_str(old).unwrap();
+    assert!(old.diagnostics().is_empty());
+    let main = entry(&old, "main.rs");
+    assert!(!main.truncated());
+    assert!(!main.binary());
+    assert_eq!(main.binary_format(), None);
+    assert!(main.flags().is_empty());
+
+    let root = support::directory(&[("main.rs", b"fn main() {}\n"), ("app.py", b"print(1)\n")]);
+    let directory = Directory::new(root.path(), Builder::<Directory>::DEFAULT_READ_LIMIT).unwrap();
+    let new = Builder::new(directory).build().unwrap().analyze().unwrap();
+    let files: Vec<_> = old
+        .diff(&new)
+        .files()
+        .iter()
+        .map(|diff| (diff.path().to_str().unwrap().to_owned(), diff.change()))
+        .collect();
+    assert_eq!(
+        files,
+        [("app.py".to_owned(), gengo::analysis::Change::Added)]
+    );
+}
+
+#[cfg(feature = "git")]
+use support::{commit_at, git, git_repository};
+
+#[test]
+#[cfg(all(feature = "directory", feature = "git"))]
+fn test_config_applies_to_every_source() {
+    use gengo::DirectoryBuilder;
+
+    let large = "SELECT 1;\n".repeat(100);
+    let root = git_repository(
+        &[
+            (".gengo.toml", b"read-limit = 2048\n"),
+            ("large.sql", large.as_bytes()),
+        ],
+        false,
+    );
+    let root = root.path();
+
+    // NOTE The directory's buffer is smaller than the configured read limit.
+    let directory = DirectoryBuilder::new(root, 64).build().unwrap();
+    let git = Git::new(root, "HEAD").unwrap();
+    let analyses = [
+        Builder::new(directory).build().unwrap().analyze().unwrap(),
+        Builder::new(git).build().unwrap().analyze().unwrap(),
+    ];
+    for analysis in analyses {
+        assert_eq!(support::paths(&analysis), ["large.sql"]);
+        let large = entry(&analysis, "large.sql");
+        assert_eq!(large.size(), 1000);
+        assert!(!large.truncated());
+    }
+}
+
+#[rstest::rstest]
+#[case::loose(false)]
+#[case::packed(true)]
+#[cfg(feature = "git")]
+fn test_git_bounded_reads(#[case] gc: bool) {
+    let large = "SELECT 1;\n".repeat(1000);
+    let root = git_repository(
+        &[
+            ("large.sql", large.as_bytes()),
+            ("small.sql", b"SELECT 1;\n"),
+            ("image.png", b"not really a PNG"),
+        ],
+        gc,
+    );
+
+    let git = Git::new(root.path(), "HEAD").unwrap();
+    let gengo = Builder::new(git).read_limit(100).build().unwrap();
+    let analysis = gengo.analyze().unwrap();
+
+    let large_entry = entry(&analysis, "large.sql");
+    assert_eq!(large_entry.size(), large.len());
+    assert!(large_entry.truncated());
+    let small = entry(&analysis, "small.sql");
+    assert_eq!(small.size(), 10);
+    assert!(!small.truncated());
+    assert_eq!(
+        entry(&analysis, "image.png").binary_format(),
+        Some(gengo::BinaryFormat::Png)
+    );
+}
+
+#[test]
+#[cfg(feature = "git")]
+fn test_git_bounded_reads_of_deltas() {
+    let old = "SELECT 1;\n".repeat(1000);
+    let new = format!("{old}SELECT 2;\n");
+    let root = git_repository(&[("large.sql", old.as_bytes())], false);
+    let root = root.path();
+    support::write(root, &[("large.sql", new.as_bytes())]);
+    git(root, &["commit", "--quiet", "-am", "changed"]);
+    git(root, &["repack", "--quiet", "-adf", "--window=10"]);
+
+    // NOTE One of the versions is stored as a delta of the other.
+    for (rev, contents) in [("HEAD~", &old), ("HEAD", &new)] {
+        let git = Git::new(root, rev).unwrap();
+        let gengo = Builder::new(git).read_limit(100).build().unwrap();
+        let analysis = gengo.analyze().unwrap();
+        let large = entry(&analysis, "large.sql");
+        assert_eq!(large.size(), contents.len());
+        assert!(large.truncated());
+        assert_eq!(large.language(), Some(&gengo::Language::Sql));
+    }
+}
+
+#[rstest::rstest]
+#[case::loose(false)]
+#[case::packed(true)]
+#[cfg(feature = "git")]
+fn test_git_sha256(#[case] gc: bool) {
+    use gengo::GitBuilder;
+
+    let large = "SELECT 1;\n".repeat(100);
+    let root = support::git_repository_with_format(
+        &[
+            ("main.rs", b"fn main() {}\n"),
+            ("large.sql", large.as_bytes()),
+            (".gitattributes", b"*.rs gengo-generated\n"),
+        ],
+        gc,
+        "sha256",
+    );
+
+    let root = root.path();
+    let builders = [
+        GitBuilder::new(root, "HEAD"),
+        GitBuilder::index(root),
+        GitBuilder::worktree(root),
+    ];
+    for builder in builders {
+        let git = builder.build().unwrap();
+        let gengo = Builder::new(git).read_limit(64).build().unwrap();
+        let analysis = gengo.analyze().unwrap();
+        assert!(analysis.diagnostics().is_empty());
+
+        let main = entry(&analysis, "main.rs");
+        assert_eq!(main.language(), Some(&gengo::Language::Rust));
+        assert!(main.generated());
+        let large = entry(&analysis, "large.sql");
+        assert_eq!(large.size(), 1000);
+        assert!(large.truncated());
+    }
+}
+
+#[rstest::rstest]
+#[case::exclude(gengo::Submodules::Exclude)]
+#[case::include(gengo::Submodules::Include)]
+#[case::vendored(gengo::Submodules::Vendored)]
+#[cfg(feature = "git")]
+fn test_git_submodules(#[case] submodules: gengo::Submodules) {
+    use gengo::GitBuilder;
+    use gengo::Submodules;
+    use gengo::analysis::Stage;
+    use std::fs;
+
+    let submodule = git_repository(&[("lib.rs", b"pub fn f() {}\n")], false);
+    let root = git_repository(&[("main.rs", b"fn main() {}\n")], false);
+    let (root, submodule) = (root.path(), submodule.path().to_str().unwrap());
+    git(
+        root,
+        &["submodule", "--quiet", "add", submodule, "vendor/sub"],
+    );
+    git(root, &["submodule", "--quiet", "add", submodule, "missing"]);
+    git(root, &["commit", "--quiet", "-m", "add submodules"]);
+    // NOTE Appends a commit that isn't pinned, so the pinned commit is used.
+    fs::write(root.join("vendor/sub/new.rs"), "fn g() {}\n").unwrap();
+    git(&root.join("vendor/sub"), &["add", "."]);
+    git(
+        &root.join("vendor/sub"),
+        &["commit", "--quiet", "-m", "unpinned"],
+    );
+    // NOTE Makes a submodule look like it was never initialized.
+    fs::remove_dir_all(root.join(".git/modules/missing")).unwrap();
+    fs::remove_dir_all(root.join("missing")).unwrap();
+
+    let git = GitBuilder::new(root, "HEAD")
+        .submodules(submodules)
+        .build()
+        .unwrap();
+    let gengo = Builder::new(git).build().unwrap();
+    let analysis = gengo.analyze().unwrap();
+
+    if submodules == Submodules::Exclude {
+        assert_eq!(support::paths(&analysis), ["main.rs"]);
+        assert!(analysis.diagnostics().is_empty());
+        return;
+    }
+    assert_eq!(support::paths(&analysis), ["main.rs", "vendor/sub/lib.rs"]);
+    assert!(!entry(&analysis, "main.rs").vendored());
+    assert_eq!(
+        entry(&analysis, "vendor/sub/lib.rs").vendored(),
+        submodules == Submodules::Vendored
+    );
+    let [diagnostic] = analysis.diagnostics() else {
+        panic!("expected 1 diagnostic, got {:?}", analysis.diagnostics());
+    };
+    assert_eq!(diagnostic.path(), Some(Path::new("missing")));
+    assert_eq!(diagnostic.stage(), Stage::Walk);
+}
+
+#[test]
+#[cfg(feature = "git")]
+fn test_git_index_and_worktree() {
+    use gengo::GitBuilder;
+    use std::fs;
+
+    let root = git_repository(
+        &[
+            ("main.rs", b"fn main() {}\n"),
+            ("deleted.rs", b"fn f() {}\n"),
+            (".gitignore", b"ignored.rs\n"),
+        ],
+        false,
+    );
+    let root = root.path();
+    fs::write(root.join("staged.rs"), "fn g() {}\n").unwrap();
+    git(root, &["add", "staged.rs"]);
+    fs::remove_file(root.join("deleted.rs")).unwrap();
+    fs::write(root.join("untracked.rs"), "fn h() {}\n").unwrap();
+    fs::write(root.join("ignored.rs"), "fn i() {}\n").unwrap();
+    fs::write(
+        root.join(".gitattributes"),
+        "untracked.rs gengo-generated\n",
+    )
+    .unwrap();
+
+    let analyze = |builder: GitBuilder| {
+        let gengo = Builder::new(builder.build().unwrap()).build().unwrap();
+        let analysis = gengo.analyze().unwrap();
+        let mut paths: Vec<_> = analysis
+            .iter()
+            .filter(|(path, _)| path.extension().is_some_and(|ext| ext == "rs"))
+            .map(|(path, entry)| (path.to_str().unwrap().to_owned(), entry.generated()))
+            .collect();
+        paths.sort();
+        paths
+    };
+
+    assert_eq!(
+        analyze(GitBuilder::new(root, "HEAD")),
+        [("deleted.rs".into(), false), ("main.rs".into(), false)]
+    );
+    assert_eq!(
+        analyze(GitBuilder::index(root)),
+        [
+            ("deleted.rs".into(), false),
+            ("main.rs".into(), false),
+            ("staged.rs".into(), false)
+        ]
+    );
+    assert_eq!(
+        analyze(GitBuilder::worktree(root)),
+        [
+            ("main.rs".into(), false),
+            ("staged.rs".into(), false),
+            ("untracked.rs".into(), true)
+        ]
+    );
+}
+
+#[test]
+#[cfg(feature = "git")]
+fn test_git_pathspecs() {
+    use gengo::GitBuilder;
+
+    let root = git_repository(
+        &[
+            ("main.rs", b"fn main() {}\n"),
+            ("services/auth/lib.rs", b"fn f() {}\n"),
+            ("services/payments/lib.rs", b"fn f() {}\n"),
+            ("services/payments/schema.sql", b"SELECT 1;\n"),
+            ("services/payments/vendor/dep.rs", b"fn f() {}\n"),
+        ],
+        false,
+    );
+    let root = root.path();
+    let analyze = |builder: GitBuilder| {
+        let gengo = Builder::new(builder.build().unwrap()).build().unwrap();
+        support::paths(&gengo.analyze().unwrap())
+    };
+
+    let builder = GitBuilder::new(root, "HEAD")
+        .pathspec("services/payments")
+        .pathspec(":(exclude)services/payments/vendor");
+    assert_eq!(
+        analyze(builder),
+        ["services/payments/lib.rs", "services/payments/schema.sql"]
+    );
+    let builder = GitBuilder::new(root, "HEAD").pathspec("*.rs");
+    assert_eq!(
+        analyze(builder),
+        [
+            "main.rs",
+            "services/auth/lib.rs",
+            "services/payments/lib.rs",
+            "services/payments/vendor/dep.rs"
+        ]
+    );
+    let builder = GitBuilder::new(root, "HEAD")
+        .pathspec(":(exclude)*.sql")
+        .reroot("services/payments");
+    assert_eq!(analyze(builder), ["lib.rs", "vendor/dep.rs"]);
+}
+
+#[test]
+#[cfg(feature = "git")]
+fn test_git_lfs_pointers() {
+    let pointer = |oid: &str, size: u64| {
+        format!("version https://git-lfs.github.com/spec/v1\noid sha256:{oid}\nsize {size}\n")
+    };
+    let image_oid = "1".repeat(64);
+    let script_oid = "2".repeat(64);
+    let root = git_repository(
+        &[
+            ("main.rs", b"fn main() {}\n"),
+            ("image.png", pointer(&image_oid, 5_000_000).as_bytes()),
+            ("script", pointer(&script_oid, 32).as_bytes()),
+            (".gitattributes", b"*.png filter=lfs\nscript filter=lfs\n"),
+        ],
+        false,
+    );
+    // NOTE Only the script is in the local LFS object store.
+    let object = format!(".git/lfs/objects/22/22/{script_oid}");
+    support::write(
+        root.path(),
+        &[(&object, b"#!/usr/bin/env python3\nprint(1)\n")],
+    );
+
+    let git = Git::new(root.path(), "HEAD").unwrap();
+    let gengo = Builder::new(git).build().unwrap();
+    let analysis = gengo.analyze().unwrap();
+
+    assert!(!entry(&analysis, "main.rs").lfs());
+    let image = entry(&analysis, "image.png");
+    assert!(image.lfs());
+    assert!(image.binary());
+    assert_eq!(image.size(), 5_000_000);
+    assert_eq!(image.binary_format(), Some(gengo::BinaryFormat::Png));
+    let script = entry(&analysis, "script");
+    assert!(script.lfs());
+    assert_eq!(script.size(), 32);
+    assert_eq!(script.language(), Some(&gengo::Language::Python));
+    assert!(!script.truncated());
+}
+
+#[test]
+#[cfg(feature = "git")]
+fn test_git_changed_since() {
+    use gengo::GitBuilder;
+    use std::fs;
+
+    // NOTE Files this large are weighted by their full size.
+    let large = "SELECT 1;\n".repeat(110_000);
+    let root = git_repository(
+        &[
+            ("main.rs", b"fn main() {\n}\n"),
+            ("README.md", b"# Readme\n"),
+            (".gengo.toml", b"[detectable]\nMarkdown = true\n"),
+            ("large.sql", large.as_bytes()),
+        ],
+        false,
+    );
+    let root = root.path();
+    git(root, &["branch", "base"]);
+    git(root, &["checkout", "--quiet", "-b", "feature"]);
+    fs::write(root.join("main.rs"), "fn main() {\n    run();\n}\n").unwrap();
+    fs::write(root.join("large.sql"), format!("{large}SELECT 2;\n")).unwrap();
+    fs::write(root.join("app.py"), "print(1)\n").unwrap();
+    git(root, &["add", "."]);
+    git(root, &["commit", "--quiet", "-m", "feature"]);
+    // NOTE Files added to the base after the branch was created aren't
+    //      changes of the branch.
+    git(root, &["checkout", "--quiet", "base"]);
+    fs::write(root.join("lib.rs"), "pub fn run() {}\n").unwrap();
+    git(root, &["add", "."]);
+    git(root, &["commit", "--quiet", "-m", "base"]);
+
+    let analyze = |changed_lines| {
+        let git = GitBuilder::new(root, "feature")
+            .changed_since("base")
+            .changed_lines(changed_lines)
+            .build()
+            .unwrap();
+        support::sizes(&Builder::new(git).build().unwrap().analyze().unwrap())
+    };
+
+    let large = ("large.sql".to_string(), large.len() + 10);
+    assert_eq!(
+        analyze(false),
+        [
+            ("app.py".to_string(), 9),
+            large.clone(),
+            ("main.rs".to_string(), 25)
+        ]
+    );
+    assert_eq!(
+        analyze(true),
+        [
+            ("app.py".to_string(), 9),
+            large,
+            ("main.rs".to_string(), 11)
+        ]
+    );
+}
+
+#[test]
+#[cfg(feature = "git")]
+fn test_git_changed_lines_classification() {
+    use gengo::GitBuilder;
+
+    let code = "function f() { return 1; }\n".repeat(3_000);
+    let old = format!("{code}//# sourceMappingURL=app.js.map\n");
+    let new = format!("let x = 1;\n{old}");
+    let root = git_repository(&[("app.js", old.as_bytes())], false);
+    let root = root.path();
+    git(root, &["branch", "base"]);
+    support::write(root, &[("app.js", new.as_bytes())]);
+    git(root, &["commit", "--quiet", "-am", "changed"]);
+
+    let analyze = |changed_lines| {
+        let git = GitBuilder::new(root, "HEAD")
+            .changed_since("base")
+            .changed_lines(changed_lines)
+            .build()
+            .unwrap();
+        let gengo = Builder::new(git).read_limit(1 << 12).build().unwrap();
+        let analysis = gengo.analyze().unwrap();
+        let app = entry(&analysis, "app.js");
+        (app.size(), app.generated(), app.truncated())
+    };
+
+    // NOTE Only the size is weighted by the changed lines.
+    assert_eq!(analyze(false), (new.len(), true, true));
+    assert_eq!(analyze(true), (11, true, true));
+}
+
+#[test]
+#[cfg(feature = "git")]
+fn test_git_history() {
+    use gengo::{Classifier, HistoryBuilder, Sampling};
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Counts the files whose contents were read.
+    struct Counter(Arc<AtomicUsize>);
+
+    impl Classifier for Counter {
+        fn classify(&self, _filepath: &Path, _contents: &[u8]) -> bool {
+            self.0.fetch_add(1, Ordering::Relaxed);
+            false
+        }
+    }
+
+    let root = support::directory(&[]);
+    let root = root.path();
+    git(root, &["init", "--quiet"]);
+    let commit = |files: &[(&str, &[u8])], date: &str| {
+        support::write(root, files);
+        commit_at(root, date);
+    };
+    commit(&[("main.rs", b"fn main() {}\n")], "2024-01-01");
+    commit(&[("lib.rs", b"pub fn run() {}\n")], "2024-01-10");
+    git(root, &["tag", "v1"]);
+    commit(&[("app.py", b"print(1)\n")], "2024-02-15");
+    commit(&[("lib.rs", b"pub fn run() {\n}\n")], "2024-03-20");
+    git(root, &["tag", "-a", "-m", "v2", "v2"]);
+
+    let dates = |builder: HistoryBuilder| {
+        let history = builder.build().unwrap();
+        history
+            .samples()
+            .iter()
+            .map(|sample| (sample.date(), sample.tag().map(str::to_owned)))
+            .collect::<Vec<_>>()
+    };
+    let sample = |date: &str, tag: Option<&str>| (date.to_string(), tag.map(str::to_owned));
+    let history = || HistoryBuilder::new(root, "HEAD");
+    assert_eq!(
+        dates(history().sampling(Sampling::Commits(2))),
+        [
+            sample("2024-01-10", Some("v1")),
+            sample("2024-03-20", Some("v2"))
+        ]
+    );
+    assert_eq!(
+        dates(history().sampling(Sampling::Tags)),
+        [
+            sample("2024-01-10", Some("v1")),
+            sample("2024-03-20", Some("v2"))
+        ]
+    );
+    assert_eq!(
+        dates(history().sampling(Sampling::Interval(30 * 24 * 60 * 60))),
+        [
+            sample("2024-01-10", Some("v1")),
+            sample("2024-02-15", None),
+            sample("2024-03-20", Some("v2")),
+        ]
+    );
+    assert_eq!(
+        dates(history().since("2024-02-01")),
+        [sample("2024-02-15", None), sample("2024-03-20", Some("v2"))]
+    );
+
+    let reads = Arc::new(AtomicUsize::new(0));
+    let analyses = history()
+        .build()
+        .unwrap()
+        .analyze(|git| {
+            Builder::new(git.build()?)
+                .classifier("counter", Counter(Arc::clone(&reads)))
+                .build()
+        })
+        .unwrap();
+    let sizes: Vec<_> = analyses
+        .iter()
+        .map(|(_, analysis)| support::sizes(analysis))
+        .collect();
+    let size = |path: &str, size| (path.to_string(), size);
+    assert_eq!(
+        sizes,
+        [
+            vec![size("main.rs", 13)],
+            vec![size("lib.rs", 16), size("main.rs", 13)],
+            vec![size("app.py", 9), size("lib.rs", 16), size("main.rs", 13)],
+            vec![size("app.py", 9), size("lib.rs", 17), size("main.rs", 13)],
+        ]
+    );
+    // NOTE Only the added or modified blob of each commit is read.
+    assert_eq!(reads.load(Ordering::Relaxed), 4);
+
+    let (newest, _) = analyses.last().unwrap();
+    assert_eq!(newest.id().len(), 40);
+    assert_eq!(newest.short_id().len(), 7);
+    assert!(newest.id().starts_with(&newest.short_id()));
+}
+
+#[test]
+#[cfg(all(feature = "git", unix))]
+fn test_git_symlinks() {
+    use gengo::analysis::DiagnosticKind;
+    use gengo::{GitBuilder, SymlinkPolicy};
+    use std::os::unix::fs::symlink;
+
+    let root = git_repository(
+        &[
+            ("src/main.rs", b"fn main() {}\n"),
+            ("docs/guide.md", b"# Guide\n"),
+        ],
+        false,
+    );
+    let root = root.path();
+    symlink("src/main.rs", root.join("link.rs")).unwrap();
+    symlink("../docs/guide.md", root.join("src/guide.md")).unwrap();
+    symlink("src", root.join("linked")).unwrap();
+    symlink("linked/main.rs", root.join("nested.rs")).unwrap();
+    symlink("cycle.rs", root.join("cycle.rs")).unwrap();
+    symlink("../outside.rs", root.join("outside.rs")).unwrap();
+    git(root, &["add", "."]);
+    git(root, &["commit", "--quiet", "-m", "symlinks"]);
+
+    let analyze = |symlinks| {
+        let git = GitBuilder::new(root, "HEAD")
+            .symlinks(symlinks)
+            .build()
+            .unwrap();
+        Builder::new(git).build().unwrap().analyze().unwrap()
+    };
+    let entry = |analysis: &gengo::Analysis, path: &str| {
+        find(analysis, path).map(|entry| (entry.symlink(), entry.size()))
+    };
+
+    let skipped = analyze(SymlinkPolicy::Skip);
+    assert_eq!(skipped.iter().count(), 2);
+    assert!(skipped.diagnostics().is_empty());
+
+    let text = analyze(SymlinkPolicy::Text);
+    assert_eq!(entry(&text, "link.rs"), Some((true, 11)));
+    assert_eq!(entry(&text, "src/main.rs"), Some((false, 13)));
+    assert!(text.diagnostics().is_empty());
+
+    let resolved = analyze(SymlinkPolicy::Resolve);
+    assert_eq!(entry(&resolved, "link.rs"), Some((true, 13)));
+    assert_eq!(entry(&resolved, "src/guide.md"), Some((true, 8)));
+    assert_eq!(entry(&resolved, "nested.rs"), Some((true, 13)));
+    // NOTE Links to directories are skipped, since their files are analyzed.
+    assert_eq!(entry(&resolved, "linked"), None);
+    let mut diagnostics: Vec<_> = resolved
+        .diagnostics()
+        .iter()
+        .map(|diagnostic| (diagnostic.path().unwrap().to_owned(), diagnostic.kind()))
+        .collect();
+    diagnostics.sort_by(|a, b| a.0.cmp(&b.0));
+    assert_eq!(
+        diagnostics,
+        [
+            ("cycle.rs".into(), DiagnosticKind::Other),
+            ("outside.rs".into(), DiagnosticKind::NotFound),
+        ]
+    );
+
+    let git = GitBuilder::worktree(root)
+        .symlinks(SymlinkPolicy::Resolve)
+        .build()
+        .unwrap();
+    let worktree = Builder::new(git).build().unwrap().analyze().unwrap();
+    assert_eq!(entry(&worktree, "nested.rs"), Some((true, 13)));
+    assert_eq!(entry(&worktree, "linked"), None);
+    assert!(
+        worktree
+            .diagnostics()
+            .iter()
+            .all(|diagnostic| diagnostic.path() != Some(Path::new("linked")))
+    );
+}
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+/// An in-memory file source that records the offset and length of each read
+/// of each file.
+struct Recording {
+    files: Vec<(&'static str, Vec<u8>)>,
+    reads: Reads,
+}
+
+/// The path, offset, and length of each read.
+type Reads = std::sync::Arc<std::sync::Mutex<Vec<(&'static str, usize, usize)>>>;
+
+impl<'fs> gengo::FileSource<'fs> for Recording {
+    type Entry = usize;
+    type Filepath = &'static str;
+    type Contents = Vec<u8>;
+    type State = ();
+    type Iter = std::ops::Range<usize>;
+
+    fn entries(&'fs self) -> Result<Self::Iter> {
+        Ok(0..self.files.len())
+    }
+
+    fn filepath(&'fs self, entry: &usize, _state: &mut ()) -> Result<&'static str> {
+        Ok(self.files[*entry].0)
+    }
+
+    fn contents(&'fs self, entry: &usize, state: &mut ()) -> Result<Vec<u8>> {
+        self.contents_prefix(entry, state, usize::MAX)
+    }
+
+    fn contents_prefix(&'fs self, entry: &usize, state: &mut (), limit: usize) -> Result<Vec<u8>> {
+        self.contents_range(entry, state, 0, limit)
+    }
+
+    fn contents_range(
+        &'fs self,
+        entry: &usize,
+        _state: &mut (),
+        offset: usize,
+        limit: usize,
+    ) -> Result<Vec<u8>> {
+        let (path, contents) = &self.files[*entry];
+        self.reads.lock().unwrap().push((path, offset, limit));
+        let end = offset.saturating_add(limit).min(contents.len());
+        Ok(contents[offset.min(end)..end].to_vec())
+    }
+
+    fn size(&'fs self, entry: &usize, _state: &mut ()) -> Result<Option<u64>> {
+        Ok(Some(self.files[*entry].1.len() as u64))
+    }
+
+    fn state(&'fs self) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_lazy_contents() {
+    let trailer = b"//# sourceMappingURL=app.js.map\n";
+    let inline = format!(
+        "//# sourceMappingURL=data:application/json;base64,{}\n",
+        "A".repeat(3000)
+    );
+    let source = Recording {
+        files: vec![
+            ("image.png", vec![0; 100_000]),
+            ("tool.png", [b"\x7fELF".as_slice(), &[0; 100_000]].concat()),
+            ("unknown.zzz", b"hello\n".repeat(10_000)),
+            ("header.h", b"int x;\n".repeat(10_000)),
+            ("main.rs", b"fn main() {}\n".repeat(10_000)),
+            (
+                "app.js",
+                [b"console.log(1);\n".repeat(10_000), trailer.to_vec()].concat(),
+            ),
+            (
+                "inline.js",
+                [
+                    b"console.log(1);\n".repeat(10_000),
+                    inline.clone().into_bytes(),
+                ]
+                .concat(),
+            ),
+        ],
+        reads: Default::default(),
+    };
+    let reads = source.reads.clone();
+    let gengo = Builder::new(source).read_limit(1 << 16).build().unwrap();
+    let analysis = gengo.analyze().unwrap();
+    let reads = |path: &str| -> Vec<(usize, usize)> {
+        reads
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(p, _, _)| *p == path)
+            .map(|(_, offset, limit)| (*offset, *limit))
+            .collect()
+    };
+
+    // NOTE Binary files are only read for their magic number, which comes
+    //      before the extension.
+    assert_eq!(reads("image.png"), [(0, 4096)]);
+    let image = entry(&analysis, "image.png");
+    assert!(image.binary());
+    assert_eq!(image.binary_format(), Some(gengo::BinaryFormat::Png));
+    assert_eq!(
+        entry(&analysis, "tool.png").binary_format(),
+        Some(gengo::BinaryFormat::Elf)
+    );
+    // NOTE Unknown files only need to be sniffed before they're dropped.
+    assert_eq!(reads("unknown.zzz"), [(0, 8000)]);
+    assert!(find(&analysis, "unknown.zzz").is_none());
+    // NOTE Ambiguous files need the whole read limit for heuristics. Only the
+    //      bytes that weren't sniffed are read again.
+    assert_eq!(reads("header.h"), [(0, 8000), (8000, (1 << 16) - 8000)]);
+    assert!(entry(&analysis, "header.h").truncated());
+    // NOTE Other files only need the window that is scored for minification.
+    assert_eq!(reads("main.rs"), [(0, 8000), (8000, (1 << 15) - 8000)]);
+    let main = entry(&analysis, "main.rs");
+    assert!(!main.generated());
+    assert!(!main.truncated());
+    // NOTE Scripts also need their end for source map trailers.
+    let size = 16 * 10_000 + trailer.len();
+    assert_eq!(
+        reads("app.js"),
+        [(0, 8000), (8000, (1 << 15) - 8000), (size - 1024, 1024)]
+    );
+    assert!(entry(&analysis, "app.js").generated());
+    // NOTE More of the end is read until it holds the last lines.
+    let size = 16 * 10_000 + inline.len();
+    assert_eq!(
+        reads("inline.js")[2..],
+        [
+            (size - 1024, 1024),
+            (size - 2048, 1024),
+            (size - 4096, 2048)
+        ]
+    );
+    assert!(entry(&analysis, "inline.js").generated());
+}
@@ -0,0 +1,122 @@
+//! Fixtures shared by the integration tests. Each fixture lives in its own
+//! temporary directory, which is removed when it's dropped, so tests can run
+//! in parallel.
+use gengo::{Analysis, Entry};
+use std::fs;
+use std::path::Path;
+use tempfile::TempDir;
+
+/// Creates a directory with the given files.
+pub fn directory(files: &[(&str, &[u8])]) -> TempDir {
+    let root = tempfile::tempdir().unwrap();
+    write(root.path(), files);
+    root
+}
+
+/// Writes files under `root`, creating their parent directories.
+pub fn write(root: &Path, files: &[(&str, &[u8])]) {
+    for (path, contents) in files {
+        let path = root.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+}
+
+/// Finds the entry of a file.
+pub fn find<'a>(analysis: &'a Analysis, path: &str) -> Option<&'a Entry> {
+    analysis
+        .iter()
+        .find(|(p, _)| p.as_path() == Path::new(path))
+        .map(|(_, entry)| entry)
+}
+
+/// Gets the entry of a file. Panics if the file wasn't analyzed.
+pub fn entry<'a>(analysis: &'a Analysis, path: &str) -> &'a Entry {
+    find(analysis, path).unwrap_or_else(|| panic!("{path} was not analyzed"))
+}
+
+/// The sizes of the analyzed files, sorted by path.
+pub fn sizes(analysis: &Analysis) -> Vec<(String, usize)> {
+    let mut sizes: Vec<_> = analysis
+        .iter()
+        .map(|(path, entry)| (path.to_str().unwrap().to_owned(), entry.size()))
+        .collect();
+    sizes.sort();
+    sizes
+}
+
+/// The paths of the analyzed files, sorted.
+pub fn paths(analysis: &Analysis) -> Vec<String> {
+    sizes(analysis).into_iter().map(|(path, _)| path).collect()
+}
+
+/// Runs a git command in `root`.
+#[cfg(feature = "git")]
+pub fn git(root: &Path, args: &[&str]) {
+    git_at(root, args, None);
+}
+
+/// Commits everything in `root` with a fixed date, like `2024-01-31`.
+#[cfg(feature = "git")]
+pub fn commit_at(root: &Path, date: &str) {
+    git(root, &["add", "."]);
+    git_at(root, &["commit", "--quiet", "-m", date], Some(date));
+}
+
+/// Runs a git command in `root`, with a fixed author and committer date if
+/// one is given.
+#[cfg(feature = "git")]
+fn git_at(root: &Path, args: &[&str], date: Option<&str>) {
+    let mut command = std::process::Command::new("git");
+    command
+        .args([
+            "-c",
+            "user.name=gengo",
+            "-c",
+            "user.email=gengo@example.com",
+            "-c",
+            "protocol.file.allow=always",
+        ])
+        .args(args)
+        .current_dir(root);
+    if let Some(date) = date {
+        let date = format!("{date}T00:00:00Z");
+        command
+            .env("GIT_AUTHOR_DATE", &date)
+            .env("GIT_COMMITTER_DATE", &date);
+    }
+    let status = command.output().unwrap().status;
+    assert!(status.success(), "git {args:?} failed");
+}
+
+/// Creates a git repository with the given files committed.
+#[cfg(feature = "git")]
+pub fn git_repository(files: &[(&str, &[u8])], gc: bool) -> TempDir {
+    git_repository_with_format(files, gc, "sha1")
+}
+
+/// Creates a git repository with the given files committed, using an object
+/// format like `sha256`.
+#[cfg(feature = "git")]
+pub fn git_repository_with_format(
+    files: &[(&str, &[u8])],
+    gc: bool,
+    object_format: &str,
+) -> TempDir {
+    let root = directory(files);
+    let path = root.path();
+    git(
+        path,
+        &[
+            "init",
+            "--quiet",
+            &format!("--object-format={object_format}"),
+        ],
+    );
+    git(path, &["add", "."]);
+    git(path, &["commit", "--quiet", "-m", "initial"]);
+    if gc {
+        git(path, &["gc", "--quiet"]);
+    }
+    root
+}